@@ -0,0 +1,118 @@
+use rust_ai_minesweeper::game_logic::*;
+use std::env;
+use std::process;
+use std::time::{Duration, Instant};
+
+// Name, height, width and number of mines of the standard board sizes
+const CONFIGS: [(&str, usize, usize, usize); 3] = [
+    ("beginner", 9, 9, 10),
+    ("intermediate", 16, 16, 40),
+    ("expert", 16, 30, 99),
+];
+const DEFAULT_GAMES: u64 = 1000;
+// Playouts of the Monte Carlo guesser per guess
+const DEFAULT_ITERATIONS: usize = 200;
+// Values accepted by --engine
+#[cfg(not(feature = "sat"))]
+const ENGINES: &str = "subset|enumeration|gaussian";
+#[cfg(feature = "sat")]
+const ENGINES: &str = "subset|enumeration|gaussian|sat";
+
+struct Options {
+    games: u64,
+    seed: u64,
+    threads: usize,
+    engine: InferenceEngine,
+    guess_policy: GuessPolicy,
+    iterations: usize,
+    depth: usize,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: benchmark [--games N] [--seed S] [--threads T] [--engine {}] [--guess min-risk|progress|monte-carlo] [--iterations N] [--depth D]",
+        ENGINES
+    );
+    process::exit(2);
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        games: DEFAULT_GAMES,
+        seed: 0,
+        threads: 0,
+        engine: InferenceEngine::Enumeration,
+        guess_policy: GuessPolicy::MinRisk,
+        iterations: DEFAULT_ITERATIONS,
+        depth: usize::MAX,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match (arg.as_str(), value.as_str()) {
+            ("--games", games) => options.games = games.parse().unwrap_or_else(|_| usage()),
+            ("--seed", seed) => options.seed = seed.parse().unwrap_or_else(|_| usage()),
+            ("--threads", threads) => options.threads = threads.parse().unwrap_or_else(|_| usage()),
+            ("--engine", "subset") => options.engine = InferenceEngine::Subset,
+            ("--engine", "enumeration") => options.engine = InferenceEngine::Enumeration,
+            ("--engine", "gaussian") => options.engine = InferenceEngine::Gaussian,
+            #[cfg(feature = "sat")]
+            ("--engine", "sat") => options.engine = InferenceEngine::Sat,
+            ("--guess", "min-risk") => options.guess_policy = GuessPolicy::MinRisk,
+            ("--guess", "progress") => options.guess_policy = GuessPolicy::Progress,
+            ("--guess", "monte-carlo") => {
+                // Filled in with the budget once every option is known
+                options.guess_policy = GuessPolicy::MonteCarlo(SearchBudget {
+                    iterations: 0,
+                    time: Duration::MAX,
+                    depth: 0,
+                })
+            }
+            ("--iterations", iterations) => {
+                options.iterations = iterations.parse().unwrap_or_else(|_| usage())
+            }
+            ("--depth", depth) => options.depth = depth.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+    if let GuessPolicy::MonteCarlo(budget) = &mut options.guess_policy {
+        budget.iterations = options.iterations;
+        budget.depth = options.depth;
+    }
+    options
+}
+
+fn main() -> Result<(), MinesweeperError> {
+    /* Plays seeded games on every standard board size and reports how well the AI does */
+    let options = parse_options();
+    println!(
+        "{:<14}{:>8}{:>10}{:>10}{:>10}{:>12}",
+        "board", "games", "win rate", "guesses", "moves", "time (s)"
+    );
+
+    for (name, height, width, num_of_mines) in CONFIGS {
+        let start = Instant::now();
+        // Game i of every run uses the same board, so results can be compared across changes
+        let mut simulation = Simulation::new(height, width, num_of_mines, options.games);
+        simulation.seed = options.seed;
+        simulation.threads = options.threads;
+        let reports = simulation.run(|| {
+            let mut ai = MinesweeperAI::new(height, width, num_of_mines)?;
+            ai.engine = options.engine;
+            ai.guess_policy = options.guess_policy;
+            Ok(ai)
+        })?;
+
+        let summary = SimulationSummary::new(&reports);
+        println!(
+            "{:<14}{:>8}{:>9.1}%{:>10.2}{:>10.1}{:>12.2}",
+            name,
+            summary.games,
+            100.0 * summary.win_rate(),
+            summary.average_guesses(),
+            summary.average_moves(),
+            start.elapsed().as_secs_f64()
+        );
+    }
+    Ok(())
+}
@@ -2,41 +2,411 @@
 Minesweeper Gaming Logic
 */
 
+#[cfg(feature = "sat")]
+pub mod sat;
+
 pub mod game_logic {
     use core::fmt;
+    use rand::distributions::{Distribution, WeightedIndex};
     use rand::seq::SliceRandom;
     use rand::thread_rng;
     use rand::Rng;
-    use std::collections::HashSet;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::collections::HashMap;
+    use std::collections::VecDeque;
+    use std::ops::AddAssign;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[cfg(feature = "sat")]
+    use crate::sat::{Lit, SatSolver};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MinesweeperError {
+        // The board needs at least one row and one column
+        InvalidDimensions { height: usize, width: usize },
+        // The board needs at least one cell that is not a mine
+        TooManyMines { num_of_mines: usize, cells: usize },
+        // The cell lies outside of the board
+        OutOfBounds((usize, usize)),
+        // The mine count of a cell contradicts what is already known about the board
+        InconsistentKnowledge { cell: (usize, usize), count: usize },
+    }
+
+    impl fmt::Display for MinesweeperError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                MinesweeperError::InvalidDimensions { height, width } => {
+                    write!(f, "invalid board dimensions {}x{}", height, width)
+                }
+                MinesweeperError::TooManyMines {
+                    num_of_mines,
+                    cells,
+                } => write!(
+                    f,
+                    "{} mines do not fit on a board of {} cells",
+                    num_of_mines, cells
+                ),
+                MinesweeperError::OutOfBounds(cell) => {
+                    write!(f, "cell {:?} is outside of the board", cell)
+                }
+                MinesweeperError::InconsistentKnowledge { cell, count } => write!(
+                    f,
+                    "mine count {} for cell {:?} contradicts the knowledge base",
+                    count, cell
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for MinesweeperError {}
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum FirstClick {
+        // Mines may be placed anywhere, including under the first chosen cell
+        Unprotected,
+        // The first chosen cell is never a mine
+        Safe,
+        // The first chosen cell and all of its neighbours are free of mines
+        Opening,
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    pub struct CellSet {
+        /* A set of cells of a board, kept as one bit per cell in row-major order.
+        Sets combined with each other must belong to boards of the same size */
+        height: usize,
+        width: usize,
+        bits: Vec<u64>,
+    }
+
+    impl CellSet {
+        pub fn new(height: usize, width: usize) -> CellSet {
+            CellSet {
+                height,
+                width,
+                bits: vec![0; (height * width).div_ceil(64)],
+            }
+        }
+
+        pub fn full(height: usize, width: usize) -> CellSet {
+            /* Returns the set of every cell on the board */
+            let mut set = CellSet::new(height, width);
+            set.bits.iter_mut().for_each(|word| *word = u64::MAX);
+            // Clear the bits past the last cell
+            let unused = set.bits.len() * 64 - height * width;
+            if let Some(last) = set.bits.last_mut() {
+                *last >>= unused;
+            }
+            set
+        }
+
+        fn index(&self, cell: Cell) -> Option<usize> {
+            if cell.0 < self.height && cell.1 < self.width {
+                Some(cell.0 * self.width + cell.1)
+            } else {
+                None
+            }
+        }
+
+        pub fn contains(&self, cell: Cell) -> bool {
+            self.index(cell)
+                .is_some_and(|index| (self.bits[index / 64] >> (index % 64)) & 1 == 1)
+        }
+
+        pub fn insert(&mut self, cell: Cell) -> bool {
+            /* Adds a cell to the set, returning whether it was not there already.
+            Cells outside of the board are left out */
+            let index = match self.index(cell) {
+                Some(index) => index,
+                None => return false,
+            };
+            let mask = 1 << (index % 64);
+            let word = &mut self.bits[index / 64];
+            let added = *word & mask == 0;
+            *word |= mask;
+            added
+        }
+
+        pub fn remove(&mut self, cell: Cell) -> bool {
+            /* Removes a cell from the set, returning whether it was there */
+            let index = match self.index(cell) {
+                Some(index) => index,
+                None => return false,
+            };
+            let mask = 1 << (index % 64);
+            let word = &mut self.bits[index / 64];
+            let removed = *word & mask != 0;
+            *word &= !mask;
+            removed
+        }
+
+        pub fn len(&self) -> usize {
+            self.bits
+                .iter()
+                .map(|word| word.count_ones() as usize)
+                .sum()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.bits.iter().all(|&word| word == 0)
+        }
+
+        pub fn clear(&mut self) {
+            self.bits.iter_mut().for_each(|word| *word = 0);
+        }
+
+        pub fn is_subset(&self, other: &CellSet) -> bool {
+            self.bits.iter().zip(&other.bits).all(|(a, b)| a & !b == 0)
+        }
+
+        pub fn is_disjoint(&self, other: &CellSet) -> bool {
+            self.bits.iter().zip(&other.bits).all(|(a, b)| a & b == 0)
+        }
+
+        pub fn union_with(&mut self, other: &CellSet) {
+            self.bits
+                .iter_mut()
+                .zip(&other.bits)
+                .for_each(|(a, b)| *a |= b);
+        }
+
+        pub fn intersect_with(&mut self, other: &CellSet) {
+            self.bits
+                .iter_mut()
+                .zip(&other.bits)
+                .for_each(|(a, b)| *a &= b);
+        }
+
+        pub fn difference_with(&mut self, other: &CellSet) {
+            self.bits
+                .iter_mut()
+                .zip(&other.bits)
+                .for_each(|(a, b)| *a &= !b);
+        }
+
+        pub fn union(&self, other: &CellSet) -> CellSet {
+            let mut set = self.clone();
+            set.union_with(other);
+            set
+        }
+
+        pub fn intersection(&self, other: &CellSet) -> CellSet {
+            let mut set = self.clone();
+            set.intersect_with(other);
+            set
+        }
+
+        pub fn difference(&self, other: &CellSet) -> CellSet {
+            let mut set = self.clone();
+            set.difference_with(other);
+            set
+        }
+
+        pub fn iter(&self) -> CellSetIter<'_> {
+            /* Iterates over the cells of the set in row-major order */
+            CellSetIter {
+                set: self,
+                word: 0,
+                bits: self.bits.first().copied().unwrap_or(0),
+            }
+        }
+    }
+
+    impl fmt::Debug for CellSet {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_set().entries(self.iter()).finish()
+        }
+    }
+
+    impl Extend<Cell> for CellSet {
+        fn extend<I: IntoIterator<Item = Cell>>(&mut self, cells: I) {
+            for cell in cells {
+                self.insert(cell);
+            }
+        }
+    }
+
+    impl<'a> IntoIterator for &'a CellSet {
+        type Item = Cell;
+        type IntoIter = CellSetIter<'a>;
+
+        fn into_iter(self) -> CellSetIter<'a> {
+            self.iter()
+        }
+    }
+
+    pub struct CellSetIter<'a> {
+        set: &'a CellSet,
+        // Word being read, and its bits not returned yet
+        word: usize,
+        bits: u64,
+    }
+
+    impl Iterator for CellSetIter<'_> {
+        type Item = Cell;
+
+        fn next(&mut self) -> Option<Cell> {
+            while self.bits == 0 {
+                self.word += 1;
+                self.bits = *self.set.bits.get(self.word)?;
+            }
+            let index = self.word * 64 + self.bits.trailing_zeros() as usize;
+            // Clear the lowest set bit
+            self.bits &= self.bits - 1;
+            Some((index / self.set.width, index % self.set.width))
+        }
+    }
 
     pub struct Minesweeper {
         pub height: usize,
         pub width: usize,
-        pub mines: HashSet<(usize, usize)>,
-        pub mines_found: HashSet<(usize, usize)>,
-        pub board: Vec<Vec<bool>>,
+        pub num_of_mines: usize,
+        pub mines: CellSet,
+        pub revealed: CellSet,
+        // First click policy and seed of a board whose mines have not been placed yet
+        pending: Option<(FirstClick, u64)>,
     }
 
     impl Minesweeper {
-        pub fn new(height: usize, width: usize, num_of_mines: usize) -> Minesweeper {
-            let mut minesweeper = Minesweeper {
+        pub fn new(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+        ) -> Result<Minesweeper, MinesweeperError> {
+            Minesweeper::with_rng(height, width, num_of_mines, &mut thread_rng())
+        }
+
+        pub fn with_seed(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+            seed: u64,
+        ) -> Result<Minesweeper, MinesweeperError> {
+            /* Builds a board whose mine layout is fully determined by `seed`. The generator
+            is ChaCha8, whose output does not change across releases of `rand` */
+            Minesweeper::with_rng(
+                height,
+                width,
+                num_of_mines,
+                &mut ChaCha8Rng::seed_from_u64(seed),
+            )
+        }
+
+        pub fn with_rng<R: Rng + ?Sized>(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+            rng: &mut R,
+        ) -> Result<Minesweeper, MinesweeperError> {
+            /* Builds a board drawing mine positions from the given random number generator */
+            let mut minesweeper = Minesweeper::empty(height, width, num_of_mines, None)?;
+            minesweeper.lay_mines(rng, &CellSet::new(height, width));
+            Ok(minesweeper)
+        }
+
+        pub fn lazy(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+            first_click: FirstClick,
+        ) -> Result<Minesweeper, MinesweeperError> {
+            Minesweeper::lazy_with_seed(
+                height,
+                width,
+                num_of_mines,
+                first_click,
+                thread_rng().gen(),
+            )
+        }
+
+        pub fn lazy_with_seed(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+            first_click: FirstClick,
+            seed: u64,
+        ) -> Result<Minesweeper, MinesweeperError> {
+            /* Builds a board without mines; they are laid out by `place_mines` once the
+            first cell is chosen, so the layout depends on both `seed` and that cell */
+            Minesweeper::empty(height, width, num_of_mines, Some((first_click, seed)))
+        }
+
+        fn empty(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+            pending: Option<(FirstClick, u64)>,
+        ) -> Result<Minesweeper, MinesweeperError> {
+            /* Builds a board without mines after checking that the configuration is playable */
+            let cells = match height.checked_mul(width) {
+                Some(cells) if cells > 0 => cells,
+                _ => return Err(MinesweeperError::InvalidDimensions { height, width }),
+            };
+            if num_of_mines >= cells {
+                return Err(MinesweeperError::TooManyMines {
+                    num_of_mines,
+                    cells,
+                });
+            }
+            Ok(Minesweeper {
                 height,
                 width,
-                mines: HashSet::new(),
-                mines_found: HashSet::new(),
-                board: vec![vec![false; width]; height],
+                num_of_mines,
+                mines: CellSet::new(height, width),
+                revealed: CellSet::new(height, width),
+                pending,
+            })
+        }
+
+        fn lay_mines<R: Rng + ?Sized>(&mut self, rng: &mut R, excluded: &CellSet) {
+            // Initialize mines in random locations, skipping excluded cells
+            let mut placed = self.mines.len();
+            while placed < self.num_of_mines {
+                let i = rng.gen_range(0..self.height);
+                let j = rng.gen_range(0..self.width);
+                if !excluded.contains((i, j)) && self.mines.insert((i, j)) {
+                    placed += 1;
+                }
+            }
+        }
+
+        pub fn mines_placed(&self) -> bool {
+            self.pending.is_none()
+        }
+
+        pub fn place_mines(&mut self, first: Cell) -> Result<(), MinesweeperError> {
+            /* Lays out the mines of a lazily generated board around the first chosen cell.
+            Does nothing if the mines have already been placed */
+            self.check(first)?;
+            let (first_click, seed) = match self.pending.take() {
+                Some(pending) => pending,
+                None => return Ok(()),
             };
-            // Initialize mines in random locations
-            let mut rng = rand::thread_rng();
-            while minesweeper.mines.len() < num_of_mines {
-                let i = rng.gen_range(0..height);
-                let j = rng.gen_range(0..width);
-                if !minesweeper.board[i][j] {
-                    minesweeper.mines.insert((i, j));
-                    minesweeper.board[i][j] = true;
+
+            let mut excluded = CellSet::new(self.height, self.width);
+            match first_click {
+                FirstClick::Unprotected => {}
+                FirstClick::Safe => {
+                    excluded.insert(first);
+                }
+                FirstClick::Opening => {
+                    for i in first.0.saturating_sub(1)..=(first.0 + 1).min(self.height - 1) {
+                        for j in first.1.saturating_sub(1)..=(first.1 + 1).min(self.width - 1) {
+                            excluded.insert((i, j));
+                        }
+                    }
                 }
             }
-            minesweeper
+            // Only protect the first cell itself when the board is too crowded for an opening
+            if self.height * self.width - excluded.len() < self.num_of_mines {
+                excluded.clear();
+                excluded.insert(first);
+            }
+
+            self.lay_mines(&mut ChaCha8Rng::seed_from_u64(seed), &excluded);
+            Ok(())
         }
 
         pub fn print(&self) {
@@ -44,7 +414,7 @@ pub mod game_logic {
             for i in 0..self.height {
                 println!("{}", "-".repeat(self.width * 2 + 1));
                 for j in 0..self.width {
-                    if self.board[i][j] {
+                    if self.mines.contains((i, j)) {
                         println!("|X");
                     } else {
                         println!("| ")
@@ -55,12 +425,29 @@ pub mod game_logic {
             println!("{}", "-".repeat(self.width * 2 + 1));
         }
 
-        pub fn is_mine(&self, cell: (usize, usize)) -> bool {
-            let (i, j) = cell;
-            self.board[i][j]
+        pub fn contains(&self, cell: (usize, usize)) -> bool {
+            cell.0 < self.height && cell.1 < self.width
+        }
+
+        fn check(&self, cell: (usize, usize)) -> Result<(), MinesweeperError> {
+            if self.contains(cell) {
+                Ok(())
+            } else {
+                Err(MinesweeperError::OutOfBounds(cell))
+            }
+        }
+
+        pub fn is_mine(&self, cell: (usize, usize)) -> Result<bool, MinesweeperError> {
+            self.check(cell)?;
+            Ok(self.mines.contains(cell))
+        }
+
+        pub fn nearby_mines(&self, cell: (usize, usize)) -> Result<usize, MinesweeperError> {
+            self.check(cell)?;
+            Ok(self.count_nearby(cell))
         }
 
-        pub fn nearby_mines(&self, cell: (usize, usize)) -> usize {
+        fn count_nearby(&self, cell: (usize, usize)) -> usize {
             /* Returns the number of mines that are within one row and column of a give cell, not including the cell itself */
 
             let (x, y) = cell;
@@ -68,7 +455,7 @@ pub mod game_logic {
 
             for i in x.saturating_sub(1)..=(x + 1).min(self.height - 1) {
                 for j in y.saturating_sub(1)..=(y + 1).min(self.width - 1) {
-                    if (i, j) != (x, y) && self.board[i][j] {
+                    if (i, j) != (x, y) && self.mines.contains((i, j)) {
                         count += 1;
                     }
                 }
@@ -76,9 +463,201 @@ pub mod game_logic {
             count
         }
 
+        pub fn reveal(&mut self, cell: Cell) -> Result<Vec<(Cell, usize)>, MinesweeperError> {
+            /* Opens a cell and, while the opened cells have no neighbouring mines, keeps
+            opening their neighbours. Returns every newly opened cell with its mine count,
+            in the order they were opened. Mines and already revealed cells open nothing */
+            self.place_mines(cell)?;
+
+            let mut opened = Vec::new();
+            if self.is_mine(cell)? || !self.revealed.insert(cell) {
+                return Ok(opened);
+            }
+
+            let mut queue = VecDeque::from([cell]);
+            while let Some((x, y)) = queue.pop_front() {
+                let count = self.count_nearby((x, y));
+                opened.push(((x, y), count));
+                if count > 0 {
+                    continue;
+                }
+                // Neighbours of a zero cell can never be mines
+                for i in x.saturating_sub(1)..=(x + 1).min(self.height - 1) {
+                    for j in y.saturating_sub(1)..=(y + 1).min(self.width - 1) {
+                        if self.revealed.insert((i, j)) {
+                            queue.push_back((i, j));
+                        }
+                    }
+                }
+            }
+            Ok(opened)
+        }
+
         pub fn won(&self) -> bool {
-            /* Check if all mines have been flagged */
-            self.mines_found == self.mines
+            /* Check if every cell that is not a mine has been revealed */
+            self.mines_placed()
+                && self.revealed.len() + self.mines.len() == self.height * self.width
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum GameStatus {
+        NotStarted,
+        Playing,
+        Won,
+        Lost,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum MoveResult {
+        // Cells opened by the move, with the number of mines around each of them
+        Opened(Vec<(Cell, usize)>),
+        Flagged(Cell),
+        Unflagged(Cell),
+        // The move set off the mine at the given cell and ended the game
+        Lost(Cell),
+        // The move had no effect, e.g. on a revealed cell or once the game is over
+        Ignored,
+    }
+
+    pub struct GameSession {
+        /* A game in progress: the board together with the player's flags
+        and the state of the game, independent of any frontend */
+        pub game: Minesweeper,
+        pub flags: CellSet,
+        // Whether winning also requires every mine to be flagged
+        pub require_flags: bool,
+        status: GameStatus,
+    }
+
+    impl GameSession {
+        pub fn new(game: Minesweeper) -> GameSession {
+            GameSession {
+                flags: CellSet::new(game.height, game.width),
+                game,
+                require_flags: false,
+                status: GameStatus::NotStarted,
+            }
+        }
+
+        pub fn status(&self) -> GameStatus {
+            self.status
+        }
+
+        pub fn is_over(&self) -> bool {
+            matches!(self.status, GameStatus::Won | GameStatus::Lost)
+        }
+
+        fn accepts(&self, cell: Cell) -> Result<bool, MinesweeperError> {
+            /* Returns whether a move on the given cell may change the game */
+            if !self.game.contains(cell) {
+                return Err(MinesweeperError::OutOfBounds(cell));
+            }
+            Ok(!self.is_over())
+        }
+
+        fn open(&mut self, cell: Cell) -> Result<Vec<(Cell, usize)>, MinesweeperError> {
+            /* Reveals a safe cell, dropping any flags wrongly placed on the cells it opens */
+            let opened = self.game.reveal(cell)?;
+            for &(opened_cell, _) in &opened {
+                self.flags.remove(opened_cell);
+            }
+            Ok(opened)
+        }
+
+        fn check_won(&mut self) {
+            /* Ends the game once every safe cell is revealed (and, if required, every mine flagged) */
+            if !self.game.won() || (self.require_flags && self.flags != self.game.mines) {
+                return;
+            }
+            self.status = GameStatus::Won;
+            // Only mines are left hidden, so flag them all
+            self.flags = self.game.mines.clone();
+        }
+
+        pub fn reveal(&mut self, cell: Cell) -> Result<MoveResult, MinesweeperError> {
+            /* Opens an unflagged cell, cascading through cells with no neighbouring mines */
+            if !self.accepts(cell)?
+                || self.flags.contains(cell)
+                || self.game.revealed.contains(cell)
+            {
+                return Ok(MoveResult::Ignored);
+            }
+
+            self.game.place_mines(cell)?;
+            self.status = GameStatus::Playing;
+            if self.game.is_mine(cell)? {
+                self.status = GameStatus::Lost;
+                return Ok(MoveResult::Lost(cell));
+            }
+            let opened = self.open(cell)?;
+            self.check_won();
+            Ok(MoveResult::Opened(opened))
+        }
+
+        pub fn flag(&mut self, cell: Cell) -> Result<MoveResult, MinesweeperError> {
+            /* Marks a hidden cell as a suspected mine. Flags are only markers for the player
+            and reveal nothing about whether the cell really is a mine */
+            if !self.accepts(cell)? || self.game.revealed.contains(cell) || !self.flags.insert(cell)
+            {
+                return Ok(MoveResult::Ignored);
+            }
+            self.check_won();
+            Ok(MoveResult::Flagged(cell))
+        }
+
+        pub fn unflag(&mut self, cell: Cell) -> Result<MoveResult, MinesweeperError> {
+            /* Removes the flag from a cell */
+            if self.accepts(cell)? && self.flags.remove(cell) {
+                Ok(MoveResult::Unflagged(cell))
+            } else {
+                Ok(MoveResult::Ignored)
+            }
+        }
+
+        pub fn toggle_flag(&mut self, cell: Cell) -> Result<MoveResult, MinesweeperError> {
+            /* Flags a hidden cell, or removes the flag if it already has one */
+            if self.flags.contains(cell) {
+                self.unflag(cell)
+            } else {
+                self.flag(cell)
+            }
+        }
+
+        pub fn chord(&mut self, cell: Cell) -> Result<MoveResult, MinesweeperError> {
+            /* Opens every unflagged neighbour of a revealed cell whose number of
+            neighbouring flags equals its number of neighbouring mines.
+            If any of the flags is wrong, one of the opened neighbours is a mine */
+            if !self.accepts(cell)? || !self.game.revealed.contains(cell) {
+                return Ok(MoveResult::Ignored);
+            }
+
+            let mut neighbours = Vec::new();
+            let mut flagged = 0;
+            for i in cell.0.saturating_sub(1)..=(cell.0 + 1).min(self.game.height - 1) {
+                for j in cell.1.saturating_sub(1)..=(cell.1 + 1).min(self.game.width - 1) {
+                    if self.flags.contains((i, j)) {
+                        flagged += 1;
+                    } else if !self.game.revealed.contains((i, j)) {
+                        neighbours.push((i, j));
+                    }
+                }
+            }
+            if neighbours.is_empty() || flagged != self.game.nearby_mines(cell)? {
+                return Ok(MoveResult::Ignored);
+            }
+
+            // A wrong flag means a mine is left among the neighbours
+            if let Some(&mine) = neighbours.iter().find(|&&c| self.game.mines.contains(c)) {
+                self.status = GameStatus::Lost;
+                return Ok(MoveResult::Lost(mine));
+            }
+            let mut opened = Vec::new();
+            for neighbour in neighbours {
+                opened.extend(self.open(neighbour)?);
+            }
+            self.check_won();
+            Ok(MoveResult::Opened(opened))
         }
     }
 
@@ -86,209 +665,976 @@ pub mod game_logic {
     pub struct Sentence {
         /* Logical statement about a  Minesweeper game
         A sentence consists of a set of board cells,
-        and a count of the number of those cells which are mines.*/
-        cells: HashSet<(usize, usize)>,
-        count: usize,
+        and bounds on the number of those cells which are mines.*/
+        cells: CellSet,
+        min: usize,
+        max: usize,
     }
 
     impl Sentence {
-        pub fn new(cells: HashSet<(usize, usize)>, count: usize) -> Sentence {
-            Sentence { cells, count }
+        pub fn new(cells: CellSet, count: usize) -> Sentence {
+            /* States that exactly `count` of the cells are mines */
+            Sentence::bounded(cells, count, count)
+        }
+
+        pub fn bounded(cells: CellSet, min: usize, max: usize) -> Sentence {
+            /* States that between `min` and `max` of the cells are mines */
+            let max = max.min(cells.len());
+            Sentence { cells, min, max }
+        }
+
+        pub fn cells(&self) -> &CellSet {
+            &self.cells
+        }
+
+        pub fn bounds(&self) -> (usize, usize) {
+            (self.min, self.max)
+        }
+
+        pub fn is_exact(&self) -> bool {
+            self.min == self.max
+        }
+
+        pub fn is_consistent(&self) -> bool {
+            /* Returns false if no number of mines among the cells satisfies the bounds */
+            self.min <= self.max
+        }
+
+        pub fn is_determined(&self) -> bool {
+            /* Returns true if the cells are known to be all safe or all mines */
+            self.max == 0 || self.min >= self.cells.len()
         }
 
-        pub fn known_mines(&self) -> HashSet<(usize, usize)> {
+        pub fn known_mines(&self) -> CellSet {
             /* Returns the set of all cell sin self.cells known to be mines */
-            // Cells are known to be mines if their set count equals the set length
-            if self.count == self.cells.len() {
+            // Cells are known to be mines if at least as many mines as cells are required
+            if self.min >= self.cells.len() {
                 self.cells.clone()
             } else {
-                HashSet::new()
+                CellSet::new(self.cells.height, self.cells.width)
             }
         }
 
-        pub fn known_safes(&self) -> HashSet<(usize, usize)> {
+        pub fn known_safes(&self) -> CellSet {
             /* Returns the set of all cells in self.cells known to be safe
-               Cells are known to be safe if their set count equals zero
+               Cells are known to be safe if at most zero of them may be mines
             */
-            if self.count == 0 {
+            if self.max == 0 {
                 self.cells.clone()
             } else {
-                HashSet::new()
+                CellSet::new(self.cells.height, self.cells.width)
             }
         }
 
         pub fn mark_mine(&mut self, cell: (usize, usize)) {
             /* Updates internal knowledge representation given the fact that a cell is known to be a mine */
-            if self.cells.contains(&cell) {
-                self.cells.remove(&cell);
-                self.count -= 1;
+            if self.cells.remove(cell) {
+                self.min = self.min.saturating_sub(1);
+                match self.max.checked_sub(1) {
+                    Some(max) => self.max = max,
+                    // The sentence allowed no mine, so its bounds are left contradicting each other
+                    None => self.min = 1,
+                }
             }
         }
 
         pub fn mark_safe(&mut self, cell: (usize, usize)) {
-            /* Updates internal knowledge representation given the fact that a cell is known to be safe */
-            if self.cells.contains(&cell) {
-                self.cells.remove(&cell);
+            /* Updates internal knowledge representation given the fact that a cell is known to be safe.
+            A sentence left with fewer cells than its least number of mines becomes inconsistent */
+            if self.cells.remove(cell) {
+                self.max = self.max.min(self.cells.len());
             }
         }
     }
     // Implementing equality comparsion
     impl PartialEq for Sentence {
         fn eq(&self, other: &Self) -> bool {
-            self.cells == other.cells && self.count == other.count
+            self.cells == other.cells && self.min == other.min && self.max == other.max
         }
     }
     // Implementing Display trait for string representation
     impl fmt::Display for Sentence {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "{:?} = {}", self.cells, self.count)
+            if self.is_exact() {
+                write!(f, "{:?} = {}", self.cells, self.min)
+            } else {
+                write!(f, "{:?} = {}..={}", self.cells, self.min, self.max)
+            }
         }
     }
 
-    type Cell = (usize, usize);
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Overlap {
+        /* What two sentences tell together about the cells they share and about the cells
+        only one of them mentions, as one sentence bounding the number of mines of each part */
+        pub shared: Sentence,
+        pub only_first: Sentence,
+        pub only_second: Sentence,
+    }
 
-    pub struct MinesweeperAI {
-        pub height: usize,
-        pub width: usize,
-        pub moves_made: HashSet<Cell>,
-        pub known_mines: HashSet<Cell>,
-        pub known_safes: HashSet<Cell>,
-        pub knowledge: Vec<Sentence>,
+    impl Overlap {
+        pub fn new(first: &Sentence, second: &Sentence) -> Overlap {
+            let shared = first.cells.intersection(&second.cells);
+            let only_first = first.cells.difference(&second.cells);
+            let only_second = second.cells.difference(&first.cells);
+            // Mines a sentence cannot fit outside of the shared cells have to be shared
+            let min = first
+                .min
+                .saturating_sub(only_first.len())
+                .max(second.min.saturating_sub(only_second.len()));
+            let max = first.max.min(second.max).min(shared.len());
+            // Whatever a sentence does not put in the shared cells is outside of them
+            Overlap {
+                only_first: Sentence::bounded(
+                    only_first,
+                    first.min.saturating_sub(max),
+                    first.max.saturating_sub(min),
+                ),
+                only_second: Sentence::bounded(
+                    only_second,
+                    second.min.saturating_sub(max),
+                    second.max.saturating_sub(min),
+                ),
+                shared: Sentence::bounded(shared, min, max),
+            }
+        }
+
+        pub fn is_consistent(&self) -> bool {
+            /* Returns false if the two sentences cannot both be true */
+            self.shared.is_consistent()
+                && self.only_first.is_consistent()
+                && self.only_second.is_consistent()
+        }
+
+        pub fn sentences(&self) -> Vec<Sentence> {
+            /* Returns the sentence of every non-empty part whose number of mines is known
+            exactly. With the first sentence containing the second one, this is the subset
+            rule; two sentences counting 1 and 2 mines with one cell each of their own
+            (the 1-2-1 pattern) make the first cell safe and the second a mine.
+            Parts that are only bounded are left out, as they follow from the two sentences */
+            [&self.shared, &self.only_first, &self.only_second]
+                .into_iter()
+                .filter(|part| !part.cells.is_empty() && part.is_exact())
+                .cloned()
+                .collect()
+        }
     }
 
-    impl MinesweeperAI {
-        pub fn new(height: usize, width: usize) -> MinesweeperAI {
-            MinesweeperAI {
+    #[derive(Clone)]
+    pub struct KnowledgeBase {
+        /* The sentences known about a board, indexed by the cells they mention so that
+        inference only revisits the sentences sharing cells with the ones that changed.
+        Sentences are kept only once, and empty sentences are dropped unless they no longer hold */
+        height: usize,
+        width: usize,
+        // Sentences by id; the slot of a removed sentence is reused by the next one added
+        sentences: Vec<Option<Sentence>>,
+        // Rule and premises each sentence was derived from, None for observed sentences
+        origins: Vec<Option<(Rule, Vec<Sentence>)>>,
+        free: Vec<usize>,
+        // Ids of the sentences mentioning each cell, by row-major cell index
+        by_cell: Vec<Vec<usize>>,
+        // Ids of the sentences added or changed since inference last looked at them
+        dirty: VecDeque<usize>,
+        queued: Vec<bool>,
+        len: usize,
+    }
+
+    impl KnowledgeBase {
+        pub fn new(height: usize, width: usize) -> KnowledgeBase {
+            KnowledgeBase {
                 height,
                 width,
-                moves_made: HashSet::new(),
-                known_mines: HashSet::new(),
-                known_safes: HashSet::new(),
-                knowledge: Vec::new(),
+                sentences: Vec::new(),
+                origins: Vec::new(),
+                free: Vec::new(),
+                by_cell: vec![Vec::new(); height * width],
+                dirty: VecDeque::new(),
+                queued: Vec::new(),
+                len: 0,
             }
         }
 
-        pub fn mark_mine(&mut self, cell: Cell) {
-            /* Marks a cell as a mine and updates all knowledge to mark that cell as a mine as well */
-            self.known_mines.insert(cell);
-            for sentence in &mut self.knowledge {
-                sentence.mark_mine(cell);
-            }
+        pub fn len(&self) -> usize {
+            self.len
         }
 
-        pub fn mark_safe(&mut self, cell: Cell) {
-            /* Marks a cell as safe, and updates all knowledge to mark that cell as safe as well */
-            self.known_safes.insert(cell);
-            for sentence in &mut self.knowledge {
-                sentence.mark_safe(cell);
-            }
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
         }
 
-        pub fn add_knowledge(&mut self, cell: Cell, mut count: usize) {
-            /* Called when the Minesweeper board tells us, for a given
-            safe cell, how many neighboring cells have mines in them.
+        pub fn iter(&self) -> impl Iterator<Item = &Sentence> {
+            self.sentences.iter().flatten()
+        }
 
-            This function should:
-                1) mark the cell as a move that has been made
-                2) mark the cell as safe
-                3) add a new sentence to the AI's knowledge base
-                   based on the value of `cell` and `count`
-                4) mark any additional cells as safe or as mines
-                   if it can be concluded based on the AI's knowledge base
-                5) add any new sentences to the AI's knowledge base
-                   if they can be inferred from existing knowledge
-            */
+        fn index(&self, cell: Cell) -> usize {
+            cell.0 * self.width + cell.1
+        }
 
-            // Step 1: Add cell to moves_made set
-            self.moves_made.insert(cell);
+        fn on_board(&self, cell: Cell) -> bool {
+            cell.0 < self.height && cell.1 < self.width
+        }
 
-            // Step 2: Mark cell as safe
-            self.mark_safe(cell);
+        fn fits(&self, cells: &CellSet) -> bool {
+            /* Returns whether a set of cells belongs to a board of the size of this one */
+            cells.height == self.height && cells.width == self.width
+        }
 
-            // Step 3: Add sentence to knowledge base by adding neighboring cells to a set
-            let mut set_cells: HashSet<Cell> = HashSet::new();
+        pub fn sentences_with(&self, cell: Cell) -> impl Iterator<Item = &Sentence> {
+            /* Returns the sentences that mention the given cell, none if it is off the board */
+            let ids: &[usize] = match self.on_board(cell) {
+                true => &self.by_cell[self.index(cell)],
+                false => &[],
+            };
+            ids.iter().filter_map(|&id| self.sentences[id].as_ref())
+        }
 
-            for i in cell.0.saturating_sub(1)..=(cell.0 + 1).min(self.height - 1) {
-                for j in cell.1.saturating_sub(1)..=(cell.1 + 1).min(self.width - 1) {
-                    // Ignore the cell itself
-                    if (i, j) == cell {
-                        continue;
-                    }
-                    // Add cell to set if cell is undetermined
-                    if !self.moves_made.contains(&(i, j))
-                        && !self.known_safes.contains(&(i, j))
-                        && !self.known_mines.contains(&(i, j))
-                    {
-                        set_cells.insert((i, j));
-                    }
-                    // Adjust count if cell is a known mine
-                    if self.known_mines.contains(&(i, j)) {
-                        count -= 1;
-                    }
-                }
+        pub fn contains(&self, sentence: &Sentence) -> bool {
+            if !self.fits(&sentence.cells) {
+                return false;
             }
-
-            // Add cells and updated mine count to knowledge base
-            if !set_cells.is_empty() {
-                self.knowledge.push(Sentence::new(set_cells.clone(), count));
+            // An equal sentence has to mention the first cell of this one
+            match sentence.cells.iter().next() {
+                Some(first) => self.sentences_with(first).any(|other| other == sentence),
+                None => false,
             }
+        }
 
-            // Loop to update knowledge until there are no more changes
-            let mut changes = true;
-            while changes{
-                changes = false;
-
-                // Step 4: Mark additional cells as safe or mines if it can be included
-                let mut safes_to_mark = Vec::new();
-                let mut mines_to_mark = Vec::new();
+        pub fn insert(&mut self, sentence: Sentence) -> bool {
+            /* Adds a sentence unless it is empty, already known, or about a board of
+            another size. Returns whether the sentence was added */
+            self.insert_with_origin(sentence, None)
+        }
 
-                for sentence in &self.knowledge {
-                    let known_safes: HashSet<(usize, usize)> = sentence.known_safes().clone();
-                    let known_mines: HashSet<(usize, usize)> = sentence.known_mines().clone();
+        fn insert_with_origin(
+            &mut self,
+            sentence: Sentence,
+            origin: Option<(Rule, Vec<Sentence>)>,
+        ) -> bool {
+            if sentence.cells.is_empty() || !self.fits(&sentence.cells) || self.contains(&sentence)
+            {
+                return false;
+            }
+            let id = match self.free.pop() {
+                Some(id) => id,
+                None => {
+                    self.sentences.push(None);
+                    self.origins.push(None);
+                    self.queued.push(false);
+                    self.sentences.len() - 1
+                }
+            };
+            self.origins[id] = origin;
+            for cell in &sentence.cells {
+                let index = self.index(cell);
+                self.by_cell[index].push(id);
+            }
+            self.sentences[id] = Some(sentence);
+            self.len += 1;
+            self.mark_dirty(id);
+            true
+        }
+
+        fn unlink(&mut self, id: usize, sentence: &Sentence) {
+            /* Forgets a sentence that has been taken out of its slot */
+            for cell in &sentence.cells {
+                let index = self.index(cell);
+                self.by_cell[index].retain(|&other| other != id);
+            }
+            self.free.push(id);
+            self.len -= 1;
+        }
+
+        fn update(&mut self, cell: Cell, is_mine: bool) {
+            /* Takes a cell whose content is known out of every sentence mentioning it */
+            if !self.on_board(cell) {
+                return;
+            }
+            let index = self.index(cell);
+            for id in std::mem::take(&mut self.by_cell[index]) {
+                let mut sentence = match self.sentences[id].take() {
+                    Some(sentence) => sentence,
+                    None => continue,
+                };
+                if is_mine {
+                    sentence.mark_mine(cell);
+                } else {
+                    sentence.mark_safe(cell);
+                }
+                // Drop the sentence if nothing is left of it or if it now repeats another one.
+                // One that no longer holds is kept, for inference to report
+                if (sentence.cells.is_empty() && sentence.is_consistent())
+                    || self.contains(&sentence)
+                {
+                    self.unlink(id, &sentence);
+                } else {
+                    self.sentences[id] = Some(sentence);
+                    self.mark_dirty(id);
+                }
+            }
+        }
+
+        pub fn mark_mine(&mut self, cell: Cell) {
+            self.update(cell, true);
+        }
+
+        pub fn mark_safe(&mut self, cell: Cell) {
+            self.update(cell, false);
+        }
+
+        fn get(&self, id: usize) -> &Sentence {
+            self.sentences[id].as_ref().expect("sentence was removed")
+        }
+
+        fn origin(&self, id: usize) -> Option<&(Rule, Vec<Sentence>)> {
+            self.origins[id].as_ref()
+        }
+
+        fn mark_dirty(&mut self, id: usize) {
+            if !self.queued[id] {
+                self.queued[id] = true;
+                self.dirty.push_back(id);
+            }
+        }
+
+        fn pop_dirty(&mut self) -> Option<usize> {
+            /* Returns the id of the next sentence waiting to be looked at */
+            while let Some(id) = self.dirty.pop_front() {
+                self.queued[id] = false;
+                if self.sentences[id].is_some() {
+                    return Some(id);
+                }
+            }
+            None
+        }
+
+        fn overlapping(&self, id: usize) -> Vec<usize> {
+            /* Returns the ids of the other sentences sharing at least one cell with a sentence */
+            let mut ids: Vec<usize> = self
+                .get(id)
+                .cells
+                .iter()
+                .flat_map(|cell| self.by_cell[self.index(cell)].iter().copied())
+                .filter(|&other| other != id)
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids
+        }
+    }
+
+    type Cell = (usize, usize);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum InferenceEngine {
+        // Only the counting, subset and overlap rules on the sentences of the knowledge base
+        Subset,
+        // The rules above, then an exhaustive search of the assignments of mines to the frontier
+        Enumeration,
+        // The rules above, then row reduction of the sentences taken as linear equations
+        Gaussian,
+        // Enumeration, with groups of frontier cells too large to enumerate handed to a SAT solver
+        #[cfg(feature = "sat")]
+        Sat,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum GuessPolicy {
+        // The cell least likely to be a mine
+        MinRisk,
+        // Among the cells nearly as unlikely to be mines as the safest one, the cell whose
+        // number is expected to make the most other cells known to be safe
+        Progress,
+        // The guess that wins most often in a Monte Carlo tree search over sampled boards
+        MonteCarlo(SearchBudget),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SearchBudget {
+        /* Bounds on the work spent on one guess; the search stops at whichever comes first */
+        pub iterations: usize,
+        pub time: Duration,
+        // Guesses a playout survives before it is counted as a win, so that playouts
+        // need not go on to the end of the game; usize::MAX plays every board out
+        pub depth: usize,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Rule {
+        // A sentence whose cells are either all safe or all mines
+        Count,
+        // A sentence left over once a sentence it contains is taken out of another one
+        Subset,
+        // A sentence bounded by two sentences sharing some of their cells
+        Overlap,
+        // The number of mines left on the board
+        GlobalCount,
+        // Every assignment of mines to a group of frontier cells, given the mines left
+        Enumeration,
+        // The row reduced sentences about a group of frontier cells
+        Gaussian,
+        // The SAT solver, on a group of frontier cells too large to enumerate
+        #[cfg(feature = "sat")]
+        Sat,
+    }
+
+    impl fmt::Display for Rule {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let name = match self {
+                Rule::Count => "counting rule",
+                Rule::Subset => "subset rule",
+                Rule::Overlap => "overlap rule",
+                Rule::GlobalCount => "number of mines left",
+                Rule::Enumeration => "enumeration of the frontier",
+                Rule::Gaussian => "row reduction of the frontier",
+                #[cfg(feature = "sat")]
+                Rule::Sat => "SAT solver",
+            };
+            write!(f, "{}", name)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Deduction {
+        /* Why the AI knows a cell to be safe or a mine */
+        pub cell: Cell,
+        pub is_mine: bool,
+        pub rule: Rule,
+        // The sentences the rule was applied to, as they stood at the time
+        pub premises: Vec<Sentence>,
+    }
+
+    impl fmt::Display for Deduction {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let premises: Vec<String> = self.premises.iter().map(|p| p.to_string()).collect();
+            write!(
+                f,
+                "{:?} is {} by the {}, from {}",
+                self.cell,
+                if self.is_mine { "a mine" } else { "safe" },
+                self.rule,
+                premises.join(" and ")
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MineProbabilities {
+        /* The chance of being a mine of every cell that is not a move already made,
+        with known safe cells at 0 and known mines at 1 */
+        pub cells: HashMap<Cell, f64>,
+        // False if the chances are only estimated, because some group of frontier cells
+        // was too large to enumerate
+        pub exact: bool,
+    }
+
+    impl MineProbabilities {
+        pub fn get(&self, cell: Cell) -> Option<f64> {
+            self.cells.get(&cell).copied()
+        }
+    }
+
+    // Guesses at most this much more likely to be mines than the safest cell are weighed
+    // by the progress they are expected to make
+    pub const GUESS_RISK_MARGIN: f64 = 0.02;
+    // Largest number of guesses whose progress is weighed, safest first
+    pub const GUESS_CANDIDATES: usize = 8;
+    // Largest number of guesses tried at every position of the Monte Carlo search, safest first
+    pub const MONTE_CARLO_CANDIDATES: usize = 6;
+    // Weight of the least tried guesses against the most successful ones in the search
+    const MONTE_CARLO_EXPLORATION: f64 = 0.5;
+
+    #[derive(Clone)]
+    pub struct MinesweeperAI {
+        pub height: usize,
+        pub width: usize,
+        pub moves_made: CellSet,
+        pub known_mines: CellSet,
+        pub known_safes: CellSet,
+        pub knowledge: KnowledgeBase,
+        pub engine: InferenceEngine,
+        pub guess_policy: GuessPolicy,
+        pub num_of_mines: usize,
+        // How each cell deduced to be safe or a mine was deduced
+        explanations: HashMap<Cell, Deduction>,
+        // Solvers kept for the groups of frontier cells the SAT engine saw last
+        #[cfg(feature = "sat")]
+        sat_groups: Vec<SatGroup>,
+    }
+
+    impl MinesweeperAI {
+        pub fn new(
+            height: usize,
+            width: usize,
+            num_of_mines: usize,
+        ) -> Result<MinesweeperAI, MinesweeperError> {
+            let cells = match height.checked_mul(width) {
+                Some(cells) if cells > 0 => cells,
+                _ => return Err(MinesweeperError::InvalidDimensions { height, width }),
+            };
+            if num_of_mines >= cells {
+                return Err(MinesweeperError::TooManyMines {
+                    num_of_mines,
+                    cells,
+                });
+            }
+            Ok(MinesweeperAI {
+                height,
+                width,
+                moves_made: CellSet::new(height, width),
+                known_mines: CellSet::new(height, width),
+                known_safes: CellSet::new(height, width),
+                knowledge: KnowledgeBase::new(height, width),
+                engine: InferenceEngine::Subset,
+                guess_policy: GuessPolicy::MinRisk,
+                num_of_mines,
+                explanations: HashMap::new(),
+                #[cfg(feature = "sat")]
+                sat_groups: Vec::new(),
+            })
+        }
+
+        pub fn global_sentence(&self) -> Sentence {
+            /* Returns the sentence stating that the mines not found yet are
+            somewhere among the cells not yet known to be safe or mines */
+            let mut cells = CellSet::full(self.height, self.width);
+            cells.difference_with(&self.known_safes);
+            cells.difference_with(&self.known_mines);
+            Sentence::new(
+                cells,
+                self.num_of_mines.saturating_sub(self.known_mines.len()),
+            )
+        }
+
+        fn global_sentences(&self) -> Option<Vec<(Sentence, Vec<Sentence>)>> {
+            /* Returns the global sentence, and what is left of it once the cells and mines
+            of sentences in the knowledge base that do not overlap each other are taken out,
+            each with the sentences it follows from. Returns None if those sentences,
+            or any one sentence, need more mines than are left */
+            let global = self.global_sentence();
+            let mut rest = global.clone();
+            let mut premises = vec![global.clone()];
+            for sentence in self.knowledge.iter() {
+                if sentence.min > global.max {
+                    return None;
+                }
+                if sentence.cells.is_subset(&rest.cells) {
+                    rest = Sentence::bounded(
+                        rest.cells.difference(&sentence.cells),
+                        rest.min.saturating_sub(sentence.max),
+                        rest.max.checked_sub(sentence.min)?,
+                    );
+                    premises.push(sentence.clone());
+                }
+            }
+            Some(vec![(global.clone(), vec![global]), (rest, premises)])
+        }
+
+        fn check(&self, cell: Cell) -> Result<(), MinesweeperError> {
+            if cell.0 < self.height && cell.1 < self.width {
+                Ok(())
+            } else {
+                Err(MinesweeperError::OutOfBounds(cell))
+            }
+        }
+
+        pub fn mark_mine(&mut self, cell: Cell) -> Result<(), MinesweeperError> {
+            /* Marks a cell as a mine and updates all knowledge to mark that cell as a mine as well */
+            self.check(cell)?;
+            self.mark(cell, true);
+            Ok(())
+        }
+
+        pub fn mark_safe(&mut self, cell: Cell) -> Result<(), MinesweeperError> {
+            /* Marks a cell as safe, and updates all knowledge to mark that cell as safe as well */
+            self.check(cell)?;
+            self.mark(cell, false);
+            Ok(())
+        }
+
+        fn mark(&mut self, cell: Cell, is_mine: bool) {
+            /* Marks a cell of the board as a mine or as safe */
+            if is_mine && self.known_mines.insert(cell) {
+                self.knowledge.mark_mine(cell);
+            } else if !is_mine && self.known_safes.insert(cell) {
+                self.knowledge.mark_safe(cell);
+            }
+        }
+
+        fn deduce(&mut self, cell: Cell, is_mine: bool, rule: Rule, premises: &[Sentence]) {
+            /* Marks a cell concluded by one of the rules, remembering why */
+            if !self.known_safes.contains(cell) && !self.known_mines.contains(cell) {
+                let deduction = Deduction {
+                    cell,
+                    is_mine,
+                    rule,
+                    premises: premises.to_vec(),
+                };
+                self.explanations.insert(cell, deduction);
+            }
+            self.mark(cell, is_mine);
+        }
+
+        pub fn explain(&self, cell: Cell) -> Option<&Deduction> {
+            /* Returns why a cell was deduced to be safe or a mine, or None if it was not.
+            Cells revealed on the board, or marked through mark_safe and mark_mine,
+            come without an explanation */
+            self.explanations.get(&cell)
+        }
+
+        fn check_count(&self, cell: Cell, count: usize) -> Result<(), MinesweeperError> {
+            /* Checks that a safe cell with `count` neighbouring mines fits the knowledge base */
+            self.check(cell)?;
+            let inconsistent = MinesweeperError::InconsistentKnowledge { cell, count };
+            if self.known_mines.contains(cell) {
+                return Err(inconsistent);
+            }
+
+            let mut mines = 0;
+            let mut undetermined = 0;
+            for i in cell.0.saturating_sub(1)..=(cell.0 + 1).min(self.height - 1) {
+                for j in cell.1.saturating_sub(1)..=(cell.1 + 1).min(self.width - 1) {
+                    if (i, j) == cell || self.known_safes.contains((i, j)) {
+                        continue;
+                    }
+                    if self.known_mines.contains((i, j)) {
+                        mines += 1;
+                    } else if !self.moves_made.contains((i, j)) {
+                        undetermined += 1;
+                    }
+                }
+            }
+            // The count has to cover the known mines, and the rest must fit in the undetermined cells
+            if count < mines || count - mines > undetermined {
+                return Err(inconsistent);
+            }
+            Ok(())
+        }
+
+        pub fn add_knowledge(&mut self, cell: Cell, count: usize) -> Result<(), MinesweeperError> {
+            /* Called when the Minesweeper board tells us, for a given
+            safe cell, how many neighboring cells have mines in them.
+
+            This function should:
+                1) mark the cell as a move that has been made
+                2) mark the cell as safe
+                3) add a new sentence to the AI's knowledge base
+                   based on the value of `cell` and `count`
+                4) mark any additional cells as safe or as mines
+                   if it can be concluded based on the AI's knowledge base
+                5) add any new sentences to the AI's knowledge base
+                   if they can be inferred from existing knowledge
+            */
+            self.check_count(cell, count)?;
+
+            // Inference may still find the count to contradict the knowledge base, in which
+            // case the AI is put back as it was before the call. Enumeration finds every such
+            // contradiction; the other engines only those their rules reach by comparing two
+            // sentences, or one sentence with the number of mines left
+            let before = (
+                self.moves_made.clone(),
+                self.known_mines.clone(),
+                self.known_safes.clone(),
+                self.knowledge.clone(),
+            );
+            let result = self.learn(cell, count);
+            if result.is_err() {
+                (
+                    self.moves_made,
+                    self.known_mines,
+                    self.known_safes,
+                    self.knowledge,
+                ) = before;
+                let (known_mines, known_safes) = (&self.known_mines, &self.known_safes);
+                self.explanations
+                    .retain(|&cell, _| known_mines.contains(cell) || known_safes.contains(cell));
+                // The solvers may hold clauses from the count, so they start over
+                #[cfg(feature = "sat")]
+                self.sat_groups.clear();
+            }
+            result
+        }
 
-                    if !known_safes.is_empty() {
-                        safes_to_mark.extend(known_safes);
-                        changes = true;
+        fn learn(&mut self, cell: Cell, mut count: usize) -> Result<(), MinesweeperError> {
+            /* Carries out the steps of add_knowledge for a count that passed check_count */
+            let inconsistent = MinesweeperError::InconsistentKnowledge { cell, count };
+
+            // Step 1: Add cell to moves_made set
+            self.moves_made.insert(cell);
+
+            // Step 2: Mark cell as safe
+            self.mark(cell, false);
+
+            // Step 3: Add sentence to knowledge base by adding neighboring cells to a set
+            let mut set_cells = CellSet::new(self.height, self.width);
+
+            for i in cell.0.saturating_sub(1)..=(cell.0 + 1).min(self.height - 1) {
+                for j in cell.1.saturating_sub(1)..=(cell.1 + 1).min(self.width - 1) {
+                    // Ignore the cell itself
+                    if (i, j) == cell {
+                        continue;
+                    }
+                    // Add cell to set if cell is undetermined
+                    if !self.moves_made.contains((i, j))
+                        && !self.known_safes.contains((i, j))
+                        && !self.known_mines.contains((i, j))
+                    {
+                        set_cells.insert((i, j));
+                    }
+                    // Adjust count if cell is a known mine
+                    if self.known_mines.contains((i, j)) {
+                        count -= 1;
                     }
-                    if !known_mines.is_empty() {
-                        mines_to_mark.extend(known_mines);
-                        changes = true;
+                }
+            }
+
+            // Add cells and updated mine count to knowledge base
+            self.knowledge.insert(Sentence::new(set_cells, count));
+
+            // Loop to update knowledge until there are no more changes
+            loop {
+                // Steps 4 and 5: Revisit every sentence added or changed since it was last
+                // looked at, marking known cells and inferring new sentences
+                while let Some(id) = self.knowledge.pop_dirty() {
+                    self.infer_from(id, inconsistent)?;
+                }
+
+                // The number of mines on the whole board gives more sentences to check
+                if self.known_mines.len() > self.num_of_mines {
+                    return Err(inconsistent);
+                }
+                let mut changes = false;
+                for (sentence, premises) in self.global_sentences().ok_or(inconsistent)? {
+                    if !sentence.is_consistent() {
+                        return Err(inconsistent);
                     }
+                    changes |= self.mark_known(&sentence, Rule::GlobalCount, &premises);
+                }
+
+                // Step 6: Once the rules above are exhausted, let the inference engine look further
+                if !changes {
+                    changes = match self.engine {
+                        InferenceEngine::Subset => false,
+                        InferenceEngine::Enumeration => self.enumerate_frontier(inconsistent)?,
+                        InferenceEngine::Gaussian => self.reduce_frontier(inconsistent)?,
+                        #[cfg(feature = "sat")]
+                        InferenceEngine::Sat => self.enumerate_frontier(inconsistent)?,
+                    };
+                }
+                if !changes {
+                    return Ok(());
                 }
-                // Apply collected changes
-                for safe in safes_to_mark {
-                    self.mark_safe(safe);
+            }
+        }
+
+        fn mark_known(&mut self, sentence: &Sentence, rule: Rule, premises: &[Sentence]) -> bool {
+            /* Marks the cells of a sentence if they are all safe or all mines, justified by
+            the given rule and premises. Returns true if any cell was marked */
+            let known_safes = sentence.known_safes();
+            let known_mines = sentence.known_mines();
+            for safe in &known_safes {
+                self.deduce(safe, false, rule, premises);
+            }
+            for mine in &known_mines {
+                self.deduce(mine, true, rule, premises);
+            }
+            !known_safes.is_empty() || !known_mines.is_empty()
+        }
+
+        fn infer_from(
+            &mut self,
+            id: usize,
+            inconsistent: MinesweeperError,
+        ) -> Result<(), MinesweeperError> {
+            /* Draws the conclusions of one sentence of the knowledge base: marks its cells if
+            they are all safe or all mines, and otherwise compares it with every sentence
+            sharing a cell with it, adding the sentences their overlap implies */
+            let sentence = self.knowledge.get(id).clone();
+            if !sentence.is_consistent() {
+                return Err(inconsistent);
+            }
+            if sentence.is_determined() {
+                // A derived sentence is explained by what it was derived from
+                let (rule, premises) = match self.knowledge.origin(id) {
+                    Some((rule, premises)) => (*rule, premises.clone()),
+                    None => (Rule::Count, vec![sentence.clone()]),
+                };
+                self.mark_known(&sentence, rule, &premises);
+                return Ok(());
+            }
+
+            for other_id in self.knowledge.overlapping(id) {
+                let overlap = Overlap::new(&sentence, self.knowledge.get(other_id));
+                if !overlap.is_consistent() {
+                    return Err(inconsistent);
                 }
-                for mine in mines_to_mark {
-                    self.mark_mine(mine);
+                let rule = if overlap.only_first.cells.is_empty()
+                    || overlap.only_second.cells.is_empty()
+                {
+                    Rule::Subset
+                } else {
+                    Rule::Overlap
+                };
+                for derived in overlap.sentences() {
+                    if !self.knowledge.contains(&derived) {
+                        let premises = vec![sentence.clone(), self.knowledge.get(other_id).clone()];
+                        self.knowledge
+                            .insert_with_origin(derived, Some((rule, premises)));
+                    }
                 }
+            }
+            Ok(())
+        }
 
-                // Remove any empty sentences from knowledge base
-                self.knowledge.retain(|sentence| !sentence.cells.is_empty());
+        fn enumerate_frontier(
+            &mut self,
+            inconsistent: MinesweeperError,
+        ) -> Result<bool, MinesweeperError> {
+            /* Marks every cell that is a mine in all, or in none, of the assignments of mines
+            consistent with the knowledge base and the number of mines left on the board.
+            Returns true if any cell was marked */
+            let mut components = Vec::new();
+            // Sentences about each enumerated group, then about each group too large for it
+            let mut groups = Vec::new();
+            let mut frontier = CellSet::new(self.height, self.width);
+            let mut skipped = 0;
+            let mut large = Vec::new();
+            for (cells, sentences) in split_frontier(self.knowledge.iter()) {
+                frontier.extend(cells.iter().cloned());
+                match FrontierComponent::enumerate(cells.clone(), &sentences) {
+                    Some(component) => {
+                        components.push(component);
+                        groups.push(sentences);
+                    }
+                    None => {
+                        skipped += cells.len();
+                        large.push((cells, sentences));
+                    }
+                }
+            }
+            let global = self.global_sentence();
+            let unconstrained: Vec<Cell> = global.cells.difference(&frontier).iter().collect();
 
-                // Step 5: Add new sentences based on inferred subset method
-                let knowledge_snapshot = self.knowledge.clone();
-                let mut new_knowledge = Vec::new();
+            // Cells outside of the enumerated groups may hold any of the remaining mines
+            let remaining = self.num_of_mines.saturating_sub(self.known_mines.len());
+            let totals =
+                restrict_mine_totals(&mut components, remaining, unconstrained.len() + skipped);
+            if totals.is_empty() {
+                return Err(inconsistent);
+            }
 
-                for s1 in &knowledge_snapshot {
-                    for s2 in &knowledge_snapshot {
-                        if s1 == s2 || s1.cells.is_subset(&s2.cells) {
-                            continue;
-                        }
-                        if s2.cells.is_subset(&s1.cells) {
-                            let difference: HashSet<Cell> = s1.cells.difference(&s2.cells).cloned().collect();
-                            if !difference.is_empty() && difference.len() < s1.cells.len() {
-                                let new_sentence = Sentence::new(difference, s1.count - s2.count);
-                                if !self.knowledge.contains(&new_sentence) && !new_knowledge.contains(&new_sentence) {
-                                    new_knowledge.push(new_sentence);
-                                    changes = true;
-                                }
-                            }
-                        }
+            // Conclusions, each with the index of the group of sentences it rests on
+            let mut deductions = Vec::new();
+            for (group, component) in components.iter().enumerate() {
+                for safe in component.safes() {
+                    deductions.push((safe, false, Rule::Enumeration, group));
+                }
+                for mine in component.mines() {
+                    deductions.push((mine, true, Rule::Enumeration, group));
+                }
+            }
+            // Groups too large to enumerate are left to the SAT solver when it is built in
+            #[cfg(feature = "sat")]
+            if self.engine == InferenceEngine::Sat {
+                let undetermined = frontier.len() + unconstrained.len();
+                // Each group picks up the solver of an earlier pass sharing a cell with it
+                let mut previous = std::mem::take(&mut self.sat_groups);
+                for (cells, sentences) in &large {
+                    let mut group = match previous
+                        .iter()
+                        .position(|group| cells.iter().any(|cell| group.vars.contains_key(cell)))
+                    {
+                        Some(i) => previous.swap_remove(i),
+                        None => SatGroup::default(),
+                    };
+                    group.fix(&self.known_mines, &self.known_safes);
+                    let other_cells = undetermined - cells.len();
+                    let (safes, mines) = group
+                        .deductions(
+                            cells,
+                            sentences,
+                            remaining.saturating_sub(other_cells),
+                            remaining,
+                        )
+                        .ok_or(inconsistent)?;
+                    self.sat_groups.push(group);
+                    for safe in safes {
+                        deductions.push((safe, false, Rule::Sat, groups.len()));
+                    }
+                    for mine in mines {
+                        deductions.push((mine, true, Rule::Sat, groups.len()));
                     }
+                    groups.push(sentences.clone());
+                }
+            }
+            // Whatever the groups hold, the rest of the mines are among the unconstrained cells,
+            // which rests on every group together with the number of mines left
+            if skipped == 0 && !unconstrained.is_empty() {
+                let is_mine = if totals.iter().all(|&total| total == remaining) {
+                    Some(false)
+                } else if totals
+                    .iter()
+                    .all(|&total| remaining - total == unconstrained.len())
+                {
+                    Some(true)
+                } else {
+                    None
+                };
+                if let Some(is_mine) = is_mine {
+                    for &cell in &unconstrained {
+                        deductions.push((cell, is_mine, Rule::Enumeration, groups.len()));
+                    }
+                    let mut everything = groups.concat();
+                    everything.push(&global);
+                    groups.push(everything);
+                }
+            }
+
+            // Only the groups some conclusion rests on are kept as premises
+            let mut premises: HashMap<usize, Vec<Sentence>> = HashMap::new();
+            for &(_, _, _, group) in &deductions {
+                premises
+                    .entry(group)
+                    .or_insert_with(|| groups[group].iter().map(|&s| s.clone()).collect());
+            }
+            let changes = !deductions.is_empty();
+            for (cell, is_mine, rule, group) in deductions {
+                self.deduce(cell, is_mine, rule, &premises[&group]);
+            }
+            Ok(changes)
+        }
+
+        fn reduce_frontier(
+            &mut self,
+            inconsistent: MinesweeperError,
+        ) -> Result<bool, MinesweeperError> {
+            /* Marks every cell that the row reduced sentences about its group of frontier
+            cells force to be safe or a mine. Returns true if any cell was marked */
+            let mut deductions = Vec::new();
+            for (cells, sentences) in split_frontier(self.knowledge.iter()) {
+                let mut system = LinearSystem::new(cells, &sentences);
+                system.reduce();
+                if !system.is_consistent() {
+                    return Err(inconsistent);
+                }
+                let (safes, mines) = (system.safes(), system.mines());
+                if safes.is_empty() && mines.is_empty() {
+                    continue;
+                }
+                let group: Vec<Sentence> = sentences.iter().map(|&s| s.clone()).collect();
+                for safe in safes {
+                    deductions.push((safe, false, group.clone()));
                 }
-                self.knowledge.extend(new_knowledge);
+                for mine in mines {
+                    deductions.push((mine, true, group.clone()));
+                }
+            }
+
+            let changes = !deductions.is_empty();
+            for (cell, is_mine, premises) in deductions {
+                self.deduce(cell, is_mine, Rule::Gaussian, &premises);
             }
+            Ok(changes)
         }
 
         pub fn make_safe_move(&self) -> Option<Cell> {
@@ -300,21 +1646,14 @@ pub mod game_logic {
             This function may use the knowledge in self.mines, self.safes
             and self.moves_made, but should not modify any of those values.
             */
-            for i in 0..self.height {
-                for j in 0..self.width {
-                    if !self.moves_made.contains(&(i, j)) && self.known_safes.contains(&(i, j)) {
-                        return Some((i, j));
-                    }
-                }
-            }
-            None
+            self.known_safes.difference(&self.moves_made).iter().next()
         }
 
         pub fn make_random_move(&self) -> Option<Cell> {
             let mut random_moves = Vec::new();
             for i in 0..self.height {
                 for j in 0..self.width {
-                    if !self.moves_made.contains(&(i, j)) && !self.known_mines.contains(&(i, j)) {
+                    if !self.moves_made.contains((i, j)) && !self.known_mines.contains((i, j)) {
                         random_moves.push((i, j));
                     }
                 }
@@ -322,5 +1661,2313 @@ pub mod game_logic {
             let mut rng = thread_rng();
             random_moves.choose(&mut rng).cloned()
         }
+
+        fn estimate_mine_probabilities(&self) -> HashMap<Cell, f64> {
+            /* Estimates the chance of being a mine for every cell that is neither a move
+            already made nor a known mine.
+            A cell mentioned by sentences is as risky as the densest of those sentences,
+            taking the middle of the bounds of a sentence as its number of mines,
+            and the mines not expected in such cells are spread evenly over the rest */
+            let mut probabilities = HashMap::new();
+            for sentence in self.knowledge.iter() {
+                let mines = (sentence.min + sentence.max) as f64 / 2.0;
+                let density = mines / sentence.cells.len() as f64;
+                for cell in &sentence.cells {
+                    let probability = probabilities.entry(cell).or_insert(0.0);
+                    if density > *probability {
+                        *probability = density;
+                    }
+                }
+            }
+
+            let mut unconstrained = Vec::new();
+            for i in 0..self.height {
+                for j in 0..self.width {
+                    if self.known_safes.contains((i, j)) {
+                        probabilities.insert((i, j), 0.0);
+                    } else if !self.known_mines.contains((i, j))
+                        && !probabilities.contains_key(&(i, j))
+                    {
+                        unconstrained.push((i, j));
+                    }
+                }
+            }
+            for cell in &self.moves_made {
+                probabilities.remove(&cell);
+            }
+
+            let remaining = self.num_of_mines.saturating_sub(self.known_mines.len()) as f64;
+            let expected: f64 = probabilities.values().sum();
+            let density = ((remaining - expected) / unconstrained.len() as f64).clamp(0.0, 1.0);
+            for cell in unconstrained {
+                probabilities.insert(cell, density);
+            }
+            probabilities
+        }
+
+        pub fn mine_probabilities(&self) -> MineProbabilities {
+            /* Returns the chance of being a mine of every cell that is not a move already
+            made, counting every layout of the remaining mines that agrees with the knowledge
+            base as equally likely. Groups of frontier cells are enumerated, and the cells no
+            sentence mentions share the mines the groups leave over. If a group needs more than
+            ENUMERATION_TALLY_LIMIT tallies, or the knowledge base is inconsistent, the chances
+            are estimated, so a call stays within a few milliseconds however large the frontier */
+            let mut components = Vec::new();
+            let mut frontier = CellSet::new(self.height, self.width);
+            let mut enumerated = true;
+            for (cells, sentences) in split_frontier(self.knowledge.iter()) {
+                frontier.extend(cells.iter().cloned());
+                match FrontierComponent::enumerate(cells, &sentences) {
+                    Some(component) => components.push(component),
+                    None => enumerated = false,
+                }
+            }
+            let unconstrained = self.global_sentence().cells.difference(&frontier);
+            let remaining = self.num_of_mines.saturating_sub(self.known_mines.len());
+
+            let exact = match enumerated {
+                true => frontier_probabilities(&components, remaining, unconstrained.len()),
+                false => None,
+            };
+            let mut cells = match &exact {
+                Some((component_probabilities, other)) => {
+                    let mut cells = HashMap::new();
+                    for (component, probabilities) in components.iter().zip(component_probabilities)
+                    {
+                        cells.extend(
+                            component
+                                .cells
+                                .iter()
+                                .cloned()
+                                .zip(probabilities.iter().cloned()),
+                        );
+                    }
+                    cells.extend(unconstrained.iter().map(|cell| (cell, *other)));
+                    for cell in self.known_safes.difference(&self.moves_made).iter() {
+                        cells.insert(cell, 0.0);
+                    }
+                    cells
+                }
+                None => self.estimate_mine_probabilities(),
+            };
+            cells.extend(self.known_mines.iter().map(|cell| (cell, 1.0)));
+            MineProbabilities {
+                cells,
+                exact: exact.is_some(),
+            }
+        }
+
+        pub fn make_probabilistic_move(&self) -> Option<Cell> {
+            /*
+            Returns the cell least likely to be a mine among those that are neither
+            moves already made nor known mines.
+            Ties go to the first such cell in row-major order.
+            */
+            let probabilities = self.mine_probabilities();
+            let mut best: Option<(Cell, f64)> = None;
+            for i in 0..self.height {
+                for j in 0..self.width {
+                    if self.known_mines.contains((i, j)) {
+                        continue;
+                    }
+                    if let Some(probability) = probabilities.get((i, j)) {
+                        if best.is_none_or(|(_, lowest)| probability < lowest) {
+                            best = Some(((i, j), probability));
+                        }
+                    }
+                }
+            }
+            best.map(|(cell, _)| cell)
+        }
+
+        pub fn make_guess(&self) -> Option<Cell> {
+            /* Returns the cell to reveal when no cell is known to be safe,
+            as picked by the guessing policy */
+            match self.guess_policy {
+                GuessPolicy::MinRisk => self.make_probabilistic_move(),
+                // Nothing is known about the first move, so a corner is the best bet to open an area
+                _ if self.moves_made.is_empty() => self.make_probabilistic_move(),
+                GuessPolicy::Progress => self.make_progressive_move(),
+                GuessPolicy::MonteCarlo(budget) => self.make_monte_carlo_move(budget),
+            }
+        }
+
+        fn safest_guesses(
+            &self,
+            probabilities: &MineProbabilities,
+            margin: f64,
+            limit: usize,
+        ) -> Vec<(Cell, f64)> {
+            /* Returns up to `limit` cells that are neither moves already made nor known mines
+            and are at most `margin` more likely to be mines than the safest of them,
+            with their chance of being a mine, safest first and then in row-major order */
+            let mut candidates: Vec<(Cell, f64)> = probabilities
+                .cells
+                .iter()
+                .filter(|(cell, _)| !self.known_mines.contains(**cell))
+                .map(|(&cell, &probability)| (cell, probability))
+                .collect();
+            candidates.sort_by(|(a, p), (b, q)| p.total_cmp(q).then(a.cmp(b)));
+            if let Some(&(_, lowest)) = candidates.first() {
+                candidates.retain(|&(_, probability)| probability <= lowest + margin);
+            }
+            candidates.truncate(limit);
+            candidates
+        }
+
+        fn make_progressive_move(&self) -> Option<Cell> {
+            /*
+            Returns the cell with the best chance of being safe times one plus the number of
+            other cells it is then expected to make known to be safe, among the few cells
+            least likely to be mines. Only cells within GUESS_RISK_MARGIN of the safest one
+            are weighed, so a much riskier cell is never picked for the progress it may make.
+            Ties go to the safest cell, then to the first in row-major order.
+            */
+            let probabilities = self.mine_probabilities();
+            let candidates =
+                self.safest_guesses(&probabilities, GUESS_RISK_MARGIN, GUESS_CANDIDATES);
+            if candidates.len() == 1 {
+                return Some(candidates[0].0);
+            }
+
+            let mut best: Option<(Cell, f64)> = None;
+            for (cell, probability) in candidates {
+                let score =
+                    (1.0 - probability) * (1.0 + self.expected_progress(cell, &probabilities));
+                if best.is_none_or(|(_, highest)| score > highest) {
+                    best = Some((cell, score));
+                }
+            }
+            best.map(|(cell, _)| cell)
+        }
+
+        fn expected_progress(&self, cell: Cell, probabilities: &MineProbabilities) -> f64 {
+            /* Returns the expected number of other cells known to be safe once a cell is
+            revealed, given that it is safe. The number it shows is taken to follow from the
+            chances of its neighbours being mines as if they were independent, and every
+            number that fits the knowledge base is played out on a copy of the AI */
+            let mut counts = vec![1.0];
+            for i in cell.0.saturating_sub(1)..=(cell.0 + 1).min(self.height - 1) {
+                for j in cell.1.saturating_sub(1)..=(cell.1 + 1).min(self.width - 1) {
+                    if (i, j) != cell {
+                        let probability = probabilities.get((i, j)).unwrap_or(0.0);
+                        counts = convolve(&counts, &[1.0 - probability, probability]);
+                    }
+                }
+            }
+
+            let known = self.known_safes.difference(&self.moves_made).len();
+            let mut progress = 0.0;
+            let mut total = 0.0;
+            for (count, chance) in counts.into_iter().enumerate() {
+                if chance <= f64::EPSILON {
+                    continue;
+                }
+                let mut ai = self.clone();
+                if ai.add_knowledge(cell, count).is_ok() {
+                    let safes = ai.known_safes.difference(&ai.moves_made).len();
+                    progress += chance * safes.saturating_sub(known) as f64;
+                    total += chance;
+                }
+            }
+            if total > 0.0 {
+                progress / total
+            } else {
+                0.0
+            }
+        }
+
+        pub fn layout_sampler(&self) -> Option<LayoutSampler> {
+            /* Prepares to draw layouts of all of the mines uniformly among those agreeing with
+            every sentence of the knowledge base, the known mines and safe cells, and the
+            number of mines on the board. Returns None if the knowledge base is inconsistent,
+            or a group of frontier cells is too entangled to count within SAMPLE_TALLY_LIMIT */
+            self.sampler_with(None)
+        }
+
+        fn sampler_with(&self, safe: Option<Cell>) -> Option<LayoutSampler> {
+            /* Prepares a layout sampler on which the given cell, if any, is safe as well */
+            let mut cells = CellSet::new(self.height, self.width);
+            cells.extend(safe);
+            let safe = Sentence::new(cells, 0);
+            let mut components = Vec::new();
+            let mut frontier = CellSet::new(self.height, self.width);
+            for (cells, sentences) in split_frontier(self.knowledge.iter().chain([&safe])) {
+                frontier.extend(cells.iter().cloned());
+                components.push(ComponentSampler::new(cells, &sentences)?);
+            }
+            let other: Vec<Cell> = self
+                .global_sentence()
+                .cells
+                .difference(&frontier)
+                .iter()
+                .collect();
+            LayoutSampler::new(
+                self.known_mines.clone(),
+                components,
+                other,
+                self.num_of_mines.saturating_sub(self.known_mines.len()),
+            )
+        }
+
+        pub fn sample_board<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Minesweeper> {
+            /* Returns a board whose mines are laid out uniformly among the layouts agreeing
+            with the knowledge base, with the moves made so far already revealed.
+            Drawing many boards is cheaper with a single `layout_sampler` */
+            let sampler = self.layout_sampler()?;
+            Some(self.board_with(sampler.sample(rng)))
+        }
+
+        fn board_with(&self, mines: CellSet) -> Minesweeper {
+            Minesweeper {
+                height: self.height,
+                width: self.width,
+                num_of_mines: self.num_of_mines,
+                mines,
+                revealed: self.moves_made.clone(),
+                pending: None,
+            }
+        }
+
+        fn make_monte_carlo_move(&self, budget: SearchBudget) -> Option<Cell> {
+            /*
+            Returns the guess most likely to win the game according to a Monte Carlo tree search.
+            Every iteration picks a guess, draws a board agreeing with the knowledge base on
+            which that guess is safe, then plays it out on a copy of the AI: safe moves are
+            made as they are found, and every later guess is picked among the safest few by
+            the UCB1 rule from the wins of earlier playouts that reached the same position.
+            A playout is won when its board is cleared, or once it has survived `budget.depth`
+            guesses. The chance of the first guess being safe is known exactly, so only its
+            chance of winning once safe is left to the playouts.
+            The boards are drawn from a generator seeded by the number of moves made, so a game
+            can be replayed as long as the search is bounded by its iterations, not its time.
+            Each guess gets a sampler of its own, built with the guess known to be safe; a guess
+            no board can be drawn for, such as one that is a mine in every layout, is dropped,
+            and so are the guesses left once the time is up. Falls back to the safest guess if
+            none is left.
+            */
+            let root = GuessNode::new(self, true);
+            if root.guesses.len() <= 1 {
+                return root.guesses.first().cloned();
+            }
+
+            let start = Instant::now();
+            let mut guesses = Vec::new();
+            let mut safe = Vec::new();
+            let mut samplers = Vec::new();
+            for (&guess, &chance) in root.guesses.iter().zip(&root.safe) {
+                if start.elapsed() >= budget.time {
+                    break;
+                }
+                if let Some(sampler) = self.sampler_with(Some(guess)) {
+                    guesses.push(guess);
+                    safe.push(chance);
+                    samplers.push(sampler);
+                }
+            }
+            if guesses.len() <= 1 {
+                return guesses
+                    .first()
+                    .cloned()
+                    .or_else(|| self.make_probabilistic_move());
+            }
+
+            let mut nodes = vec![GuessNode::with(guesses, safe)];
+            let mut rng = ChaCha8Rng::seed_from_u64(self.moves_made.len() as u64);
+            for _ in 0..budget.iterations {
+                if start.elapsed() >= budget.time {
+                    break;
+                }
+                let g = nodes[0].select();
+                let mines = samplers[g].sample(&mut rng);
+                self.play_out(&mut nodes, self.board_with(mines), g, budget.depth);
+            }
+            Some(nodes[0].guesses[nodes[0].best()])
+        }
+
+        fn play_out(
+            &self,
+            nodes: &mut Vec<GuessNode>,
+            mut board: Minesweeper,
+            first: usize,
+            depth: usize,
+        ) {
+            /* Plays one iteration of the Monte Carlo search on a sampled board, starting with
+            the given guess from the position searched, growing the tree along the way and
+            crediting every guess made with the result */
+            let mut ai = self.clone();
+            ai.guess_policy = GuessPolicy::MinRisk;
+            let mut node = 0;
+            let mut g = first;
+            let mut path = Vec::new();
+            let won = loop {
+                path.push((node, g));
+                let guess = nodes[node].guesses[g];
+
+                // Reveal the guess, then every cell the AI finds to be safe
+                let mut observations = Vec::new();
+                let mut next = Some(guess);
+                let mut alive = true;
+                while let Some(cell) = next {
+                    match board.reveal(cell) {
+                        Ok(opened) if !board.mines.contains(cell) => {
+                            for &(cell, count) in &opened {
+                                alive &= ai.add_knowledge(cell, count).is_ok();
+                            }
+                            observations.extend(opened);
+                        }
+                        _ => alive = false,
+                    }
+                    next = ai.make_safe_move().filter(|_| alive);
+                }
+
+                if !alive || board.won() || path.len() >= depth {
+                    break alive;
+                }
+                node = match nodes[node].children.get(&(g, observations.clone())) {
+                    Some(&child) => child,
+                    None => {
+                        nodes.push(GuessNode::new(&ai, false));
+                        let child = nodes.len() - 1;
+                        nodes[node].children.insert((g, observations), child);
+                        child
+                    }
+                };
+                if nodes[node].guesses.is_empty() {
+                    break false;
+                }
+                g = nodes[node].select();
+            };
+
+            for (node, g) in path {
+                nodes[node].visits[g] += 1;
+                nodes[node].wins[g] += won as u32;
+            }
+        }
+    }
+
+    struct GuessNode {
+        /* A position of the Monte Carlo search, reached from the position searched by a
+        sequence of guesses and what they opened */
+        // Guesses tried from this position, safest first, with how often each was played
+        // and how often it won
+        guesses: Vec<Cell>,
+        visits: Vec<u32>,
+        wins: Vec<u32>,
+        // Chance of each guess being safe, if its playouts are only played on boards where
+        // it is, or else 1
+        safe: Vec<f64>,
+        // Position reached by each guess, by the cells it and the safe moves after it opened
+        children: HashMap<(usize, Vec<(Cell, usize)>), usize>,
+    }
+
+    impl GuessNode {
+        fn new(ai: &MinesweeperAI, conditioned: bool) -> GuessNode {
+            /* Lists the safest guesses of the AI that may be safe at all */
+            let probabilities = ai.mine_probabilities();
+            let (guesses, safe): (Vec<Cell>, Vec<f64>) = ai
+                .safest_guesses(&probabilities, 1.0, MONTE_CARLO_CANDIDATES)
+                .into_iter()
+                .filter(|&(_, probability)| probability < 1.0)
+                .map(|(cell, probability)| match conditioned {
+                    true => (cell, 1.0 - probability),
+                    false => (cell, 1.0),
+                })
+                .unzip();
+            GuessNode::with(guesses, safe)
+        }
+
+        fn with(guesses: Vec<Cell>, safe: Vec<f64>) -> GuessNode {
+            /* Starts a position from which none of the given guesses has been tried yet */
+            GuessNode {
+                visits: vec![0; guesses.len()],
+                wins: vec![0; guesses.len()],
+                guesses,
+                safe,
+                children: HashMap::new(),
+            }
+        }
+
+        fn value(&self, g: usize) -> f64 {
+            /* Returns the estimated chance of winning with a guess that has been tried */
+            self.safe[g] * self.wins[g] as f64 / self.visits[g] as f64
+        }
+
+        fn best(&self) -> usize {
+            /* Returns the guess with the best estimated chance of winning, the safest on ties */
+            let mut best = 0;
+            for g in 1..self.guesses.len() {
+                if self.visits[g] > 0
+                    && (self.visits[best] == 0 || self.value(g) > self.value(best))
+                {
+                    best = g;
+                }
+            }
+            best
+        }
+
+        fn select(&self) -> usize {
+            /* Returns the first guess never tried, or else the one with the best
+            upper confidence bound on its chance of winning */
+            if let Some(g) = self.visits.iter().position(|&visits| visits == 0) {
+                return g;
+            }
+            let total = (self.visits.iter().sum::<u32>() as f64).ln();
+            let bound = |g: usize| {
+                self.value(g) + MONTE_CARLO_EXPLORATION * (total / self.visits[g] as f64).sqrt()
+            };
+            let mut best = 0;
+            for g in 1..self.guesses.len() {
+                if bound(g) > bound(best) {
+                    best = g;
+                }
+            }
+            best
+        }
+    }
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Move {
+        // A cell the solver has proven to be safe
+        Safe(Cell),
+        // A cell picked without knowing for sure that it is safe
+        Guess(Cell),
+    }
+
+    impl Move {
+        pub fn cell(&self) -> Cell {
+            match *self {
+                Move::Safe(cell) | Move::Guess(cell) => cell,
+            }
+        }
+    }
+
+    pub trait Solver {
+        /* A player that picks cells to reveal from the numbers seen so far */
+
+        // Called for every safe cell the board opens, with the number of mines around it
+        fn observe(&mut self, cell: Cell, count: usize) -> Result<(), MinesweeperError>;
+
+        // Returns the next cell to reveal, or None if no cell is left to try
+        fn next_move(&mut self) -> Option<Move>;
+
+        // Returns the cells the solver knows to be mines
+        fn flagged_mines(&self) -> CellSet;
+
+        // Returns the chance of being a mine of every unrevealed cell, if the solver tracks it
+        fn mine_probabilities(&self) -> Option<MineProbabilities> {
+            None
+        }
+    }
+
+    impl Solver for MinesweeperAI {
+        fn observe(&mut self, cell: Cell, count: usize) -> Result<(), MinesweeperError> {
+            self.add_knowledge(cell, count)
+        }
+
+        fn next_move(&mut self) -> Option<Move> {
+            self.make_safe_move()
+                .map(Move::Safe)
+                .or_else(|| self.make_guess().map(Move::Guess))
+        }
+
+        fn flagged_mines(&self) -> CellSet {
+            self.known_mines.clone()
+        }
+
+        fn mine_probabilities(&self) -> Option<MineProbabilities> {
+            Some(MinesweeperAI::mine_probabilities(self))
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct GameReport {
+        pub won: bool,
+        // Cells the solver chose to reveal, and how many of them were guesses
+        pub moves: usize,
+        pub guesses: usize,
+    }
+
+    pub fn play<S: Solver + ?Sized>(
+        session: &mut GameSession,
+        solver: &mut S,
+    ) -> Result<GameReport, MinesweeperError> {
+        /* Lets the solver play until the game is over, feeding it every cell that opens.
+        Stops early if the solver runs out of moves or picks a cell that cannot be revealed */
+        let mut report = GameReport::default();
+        while !session.is_over() {
+            let next = match solver.next_move() {
+                Some(next) => next,
+                None => break,
+            };
+            report.moves += 1;
+            if let Move::Guess(_) = next {
+                report.guesses += 1;
+            }
+            match session.reveal(next.cell())? {
+                MoveResult::Opened(opened) => {
+                    for (cell, count) in opened {
+                        solver.observe(cell, count)?;
+                    }
+                }
+                MoveResult::Ignored => break,
+                _ => {}
+            }
+        }
+        report.won = session.status() == GameStatus::Won;
+        Ok(report)
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Simulation {
+        /* A batch of seeded games on boards of the same size.
+        Game i is played on the board generated from `seed + i` */
+        pub height: usize,
+        pub width: usize,
+        pub num_of_mines: usize,
+        pub first_click: FirstClick,
+        pub games: u64,
+        pub seed: u64,
+        // Number of worker threads, or 0 to use every available core
+        pub threads: usize,
+    }
+
+    impl Simulation {
+        pub fn new(height: usize, width: usize, num_of_mines: usize, games: u64) -> Simulation {
+            Simulation {
+                height,
+                width,
+                num_of_mines,
+                first_click: FirstClick::Safe,
+                games,
+                seed: 0,
+                threads: 0,
+            }
+        }
+
+        pub fn play_game<S: Solver>(
+            &self,
+            game: u64,
+            solver: &mut S,
+        ) -> Result<GameReport, MinesweeperError> {
+            /* Plays game number `game` of the batch with the given solver */
+            let board = Minesweeper::lazy_with_seed(
+                self.height,
+                self.width,
+                self.num_of_mines,
+                self.first_click,
+                self.seed.wrapping_add(game),
+            )?;
+            play(&mut GameSession::new(board), solver)
+        }
+
+        pub fn run<S, F>(&self, new_solver: F) -> Result<Vec<GameReport>, MinesweeperError>
+        where
+            S: Solver,
+            F: Fn() -> Result<S, MinesweeperError> + Sync,
+        {
+            /* Plays every game of the batch, each with a fresh solver, sharing the games
+            out between worker threads. Reports come back in game order, so the results
+            do not depend on the number of threads */
+            let threads = match self.threads {
+                0 => thread::available_parallelism().map_or(1, |n| n.get()),
+                threads => threads,
+            };
+            let games = self.games as usize;
+            let mut results: Vec<Option<Result<GameReport, MinesweeperError>>> = vec![None; games];
+            let chunk_size = games.div_ceil(threads).max(1);
+
+            thread::scope(|scope| {
+                for (chunk, chunk_results) in results.chunks_mut(chunk_size).enumerate() {
+                    let new_solver = &new_solver;
+                    scope.spawn(move || {
+                        for (offset, result) in chunk_results.iter_mut().enumerate() {
+                            let game = (chunk * chunk_size + offset) as u64;
+                            *result = Some(
+                                new_solver()
+                                    .and_then(|mut solver| self.play_game(game, &mut solver)),
+                            );
+                        }
+                    });
+                }
+            });
+            // Every slot has been filled by its thread; the first failing game decides the error
+            results.into_iter().flatten().collect()
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub struct SimulationSummary {
+        pub games: usize,
+        pub wins: usize,
+        pub moves: usize,
+        pub guesses: usize,
+    }
+
+    impl SimulationSummary {
+        pub fn new(reports: &[GameReport]) -> SimulationSummary {
+            let mut summary = SimulationSummary::default();
+            for report in reports {
+                summary.games += 1;
+                summary.wins += report.won as usize;
+                summary.moves += report.moves;
+                summary.guesses += report.guesses;
+            }
+            summary
+        }
+
+        pub fn win_rate(&self) -> f64 {
+            self.wins as f64 / self.games.max(1) as f64
+        }
+
+        pub fn average_moves(&self) -> f64 {
+            self.moves as f64 / self.games.max(1) as f64
+        }
+
+        pub fn average_guesses(&self) -> f64 {
+            self.guesses as f64 / self.games.max(1) as f64
+        }
+    }
+
+    // Largest group of connected frontier cells whose assignments are counted exactly,
+    // so that every count fits in a u128
+    pub const ENUMERATION_LIMIT: usize = 120;
+    // Largest number of tallies kept when counting them, which keeps a count of the
+    // assignments within a few milliseconds
+    pub const ENUMERATION_TALLY_LIMIT: usize = 1 << 16;
+
+    pub fn split_frontier<'a>(
+        knowledge: impl IntoIterator<Item = &'a Sentence>,
+    ) -> Vec<(Vec<Cell>, Vec<&'a Sentence>)> {
+        /* Splits the cells mentioned by the knowledge base into groups that share no
+        sentence, so that each group can be solved on its own. Cells within a group
+        are sorted in row-major order */
+        let mut groups: Vec<(CellSet, Vec<&Sentence>)> = Vec::new();
+        for sentence in knowledge
+            .into_iter()
+            .filter(|sentence| !sentence.cells.is_empty())
+        {
+            // Merge every existing group that shares a cell with this sentence
+            let mut cells = sentence.cells.clone();
+            let mut sentences = vec![sentence];
+            let mut i = 0;
+            while i < groups.len() {
+                if groups[i].0.is_disjoint(&cells) {
+                    i += 1;
+                } else {
+                    let (group_cells, group_sentences) = groups.swap_remove(i);
+                    cells.union_with(&group_cells);
+                    sentences.extend(group_sentences);
+                }
+            }
+            groups.push((cells, sentences));
+        }
+
+        let mut components: Vec<(Vec<Cell>, Vec<&'a Sentence>)> = groups
+            .into_iter()
+            .map(|(cells, sentences)| (cells.iter().collect(), sentences))
+            .collect();
+        components.sort_by_key(|(cells, _)| cells[0]);
+        components
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct FrontierComponent {
+        /* A group of connected frontier cells, with a tally of every assignment
+        of mines to them that satisfies all of the sentences about them */
+        pub cells: Vec<Cell>,
+        // solutions[k] is the number of consistent assignments placing exactly k mines
+        pub solutions: Vec<u128>,
+        // mine_counts[k][c] is how many of those assignments put a mine on cells[c]
+        pub mine_counts: Vec<Vec<u128>>,
+    }
+
+    impl FrontierComponent {
+        pub fn enumerate(cells: Vec<Cell>, sentences: &[&Sentence]) -> Option<FrontierComponent> {
+            /* Tallies the consistent assignments of mines to a group of cells. Assignments
+            sharing a state are counted together, forward from the first cell assigned and
+            back from the last, so a cell is a mine in the ways to reach each state before it
+            times the ways to finish from the state its mine leads to. Returns None if the
+            group is larger than ENUMERATION_LIMIT or needs more than ENUMERATION_TALLY_LIMIT
+            tallies */
+            if cells.len() > ENUMERATION_LIMIT {
+                return None;
+            }
+            let graph = AssignmentGraph::new(&cells, sentences, ENUMERATION_TALLY_LIMIT)?;
+            let back: Vec<Vec<Vec<u128>>> = graph.count_back();
+
+            let mut mine_counts = vec![vec![0; cells.len()]; cells.len() + 1];
+            // forward[s][k] is the number of ways to reach state s with k mines placed
+            let mut forward: Vec<Vec<u128>> = vec![vec![1]];
+            for (i, &c) in graph.order.iter().enumerate() {
+                let mut next_forward = vec![vec![0; i + 2]; back[i + 1].len()];
+                for (ways, options) in forward.iter().zip(&graph.next[i]) {
+                    for (is_mine, state) in options.iter().enumerate() {
+                        let state = match *state {
+                            Some(state) => state,
+                            None => continue,
+                        };
+                        for (k, &before) in ways.iter().enumerate() {
+                            next_forward[state][k + is_mine] += before;
+                        }
+                        if is_mine == 0 {
+                            continue;
+                        }
+                        for (k, &before) in ways.iter().enumerate().filter(|(_, &n)| n > 0) {
+                            for (rest, &after) in back[i + 1][state].iter().enumerate() {
+                                mine_counts[k + 1 + rest][c] += before * after;
+                            }
+                        }
+                    }
+                }
+                forward = next_forward;
+            }
+            Some(FrontierComponent {
+                solutions: back[0][0].clone(),
+                cells,
+                mine_counts,
+            })
+        }
+
+        pub fn retain_mine_totals(&mut self, keep: impl Fn(usize) -> bool) {
+            /* Drops the assignments whose number of mines does not satisfy `keep` */
+            for (mines, count) in self.solutions.iter_mut().enumerate() {
+                if !keep(mines) {
+                    *count = 0;
+                    self.mine_counts[mines]
+                        .iter_mut()
+                        .for_each(|count| *count = 0);
+                }
+            }
+        }
+
+        pub fn is_consistent(&self) -> bool {
+            self.solutions.iter().any(|&count| count > 0)
+        }
+
+        fn cells_where(&self, is_mine: bool) -> Vec<Cell> {
+            /* Returns the cells that are mines in every (or in no) consistent assignment */
+            let total: u128 = self.solutions.iter().sum();
+            if total == 0 {
+                return Vec::new();
+            }
+            (0..self.cells.len())
+                .filter(|&c| {
+                    let mines: u128 = self.mine_counts.iter().map(|counts| counts[c]).sum();
+                    if is_mine {
+                        mines == total
+                    } else {
+                        mines == 0
+                    }
+                })
+                .map(|c| self.cells[c])
+                .collect()
+        }
+
+        pub fn safes(&self) -> Vec<Cell> {
+            self.cells_where(false)
+        }
+
+        pub fn mines(&self) -> Vec<Cell> {
+            self.cells_where(true)
+        }
+    }
+
+    fn reachable_totals(components: &[FrontierComponent], skip: Option<usize>) -> Vec<bool> {
+        /* Returns, for every number of mines, whether the components other
+        than `skip` can hold exactly that many mines between them */
+        let mut reachable = vec![true];
+        for (i, component) in components.iter().enumerate() {
+            if Some(i) == skip {
+                continue;
+            }
+            let mut next = vec![false; reachable.len() + component.cells.len()];
+            for (total, _) in reachable.iter().enumerate().filter(|(_, &r)| r) {
+                for (mines, &count) in component.solutions.iter().enumerate() {
+                    if count > 0 {
+                        next[total + mines] = true;
+                    }
+                }
+            }
+            reachable = next;
+        }
+        reachable
+    }
+
+    // Largest number of tallies kept when counting the assignments of mines to a group of
+    // frontier cells to draw from, one per state of the counts of sentences and number of mines
+    pub const SAMPLE_TALLY_LIMIT: usize = 1 << 22;
+
+    fn assignment_order(members: &[Vec<usize>], n: usize) -> Vec<usize> {
+        /* Returns the indices of a group of n cells breadth first from its first cell, going
+        from a cell to the cells it shares a sentence with in row-major order, given the
+        indices of the cells of every sentence. Cells sharing a sentence come close together,
+        so few sentences are partly assigned at any step whichever way the group runs */
+        let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for members in members {
+            for &i in members {
+                neighbours[i].extend(members);
+            }
+        }
+
+        let mut order = Vec::with_capacity(n);
+        let mut seen = vec![false; n];
+        for start in 0..n {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut queue = VecDeque::from([start]);
+            while let Some(i) = queue.pop_front() {
+                order.push(i);
+                neighbours[i].sort_unstable();
+                for &j in &neighbours[i] {
+                    if !seen[j] {
+                        seen[j] = true;
+                        queue.push_back(j);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    struct AssignmentGraph {
+        /* The consistent assignments of mines to a group of frontier cells, as paths through
+        states. The cells are assigned in turn, and at every step only the mines counted so far
+        by the sentences that are partly assigned matter to the rest; assignments leaving the
+        same counts reach the same state, which keeps large groups along a frontier tractable */
+        // Indices of the cells of the group, in the order they are assigned
+        order: Vec<usize>,
+        // next[i][s] is the state that each value of the i-th cell assigned leads to from
+        // state s, where the states before that cell are the distinct counts of the sentences
+        // partly assigned
+        next: Vec<Vec<[Option<usize>; 2]>>,
+    }
+
+    impl AssignmentGraph {
+        fn new(cells: &[Cell], sentences: &[&Sentence], limit: usize) -> Option<AssignmentGraph> {
+            /* Returns None if counting the assignments takes more than `limit` tallies,
+            one per state and number of mines left to place */
+            let n = cells.len();
+            let index: HashMap<Cell, usize> = cells
+                .iter()
+                .enumerate()
+                .map(|(c, cell)| (*cell, c))
+                .collect();
+            let members: Vec<Vec<usize>> = sentences
+                .iter()
+                .map(|sentence| sentence.cells.iter().map(|cell| index[&cell]).collect())
+                .collect();
+            let order = assignment_order(&members, n);
+            let mut step = vec![0; n];
+            for (i, &c) in order.iter().enumerate() {
+                step[c] = i;
+            }
+            // For every step, the sentences mentioning its cell with how many of their cells come after it
+            let mut cell_sentences: Vec<Vec<(usize, usize)>> = vec![Vec::new(); n];
+            // The sentences partly assigned before each step, in the order of their counts
+            let mut active: Vec<Vec<usize>> = vec![Vec::new(); n + 1];
+            for (id, members) in members.iter().enumerate() {
+                let mut steps: Vec<usize> = members.iter().map(|&c| step[c]).collect();
+                steps.sort_unstable();
+                for (k, &i) in steps.iter().enumerate() {
+                    cell_sentences[i].push((id, steps.len() - k - 1));
+                }
+                if let (Some(&first), Some(&last)) = (steps.first(), steps.last()) {
+                    for step in &mut active[first + 1..=last] {
+                        step.push(id);
+                    }
+                }
+            }
+
+            // Find every state reachable from the empty one, cell by cell
+            let mut next: Vec<Vec<[Option<usize>; 2]>> = vec![Vec::new(); n];
+            let mut keys: Vec<Vec<usize>> = vec![Vec::new()];
+            let mut tallies = n + 1;
+            for i in 0..n {
+                // The counts of a state are extended with the sentences starting at cell i,
+                // then the sentences of cell i and of the next state are found by position
+                let mut working = active[i].clone();
+                for &(id, _) in &cell_sentences[i] {
+                    if !working.contains(&id) {
+                        working.push(id);
+                    }
+                }
+                let position = |id: &usize| working.iter().position(|other| other == id);
+                let updates: Vec<(usize, &Sentence, usize)> = cell_sentences[i]
+                    .iter()
+                    .filter_map(|&(id, after)| Some((position(&id)?, sentences[id], after)))
+                    .collect();
+                let kept: Vec<usize> = active[i + 1].iter().filter_map(position).collect();
+
+                let mut states: HashMap<Vec<usize>, usize> = HashMap::new();
+                let mut next_keys = Vec::new();
+                let mut mines = Vec::with_capacity(working.len());
+                for key in &keys {
+                    let mut options = [None, None];
+                    for is_mine in [false, true] {
+                        // Mines among the assigned cells of every sentence partly assigned after cell i
+                        mines.clear();
+                        mines.extend(key);
+                        mines.resize(working.len(), 0);
+                        let mut consistent = true;
+                        for &(k, sentence, after) in &updates {
+                            mines[k] += is_mine as usize;
+                            consistent &=
+                                mines[k] <= sentence.max && mines[k] + after >= sentence.min;
+                        }
+                        if !consistent {
+                            continue;
+                        }
+                        let next_key: Vec<usize> = kept.iter().map(|&k| mines[k]).collect();
+                        let state = match states.get(&next_key) {
+                            Some(&state) => state,
+                            None => {
+                                states.insert(next_key.clone(), next_keys.len());
+                                next_keys.push(next_key);
+                                next_keys.len() - 1
+                            }
+                        };
+                        options[is_mine as usize] = Some(state);
+                    }
+                    next[i].push(options);
+                }
+                tallies += next_keys.len() * (n - i);
+                if tallies > limit {
+                    return None;
+                }
+                keys = next_keys;
+            }
+            Some(AssignmentGraph { order, next })
+        }
+
+        fn count_back<T>(&self) -> Vec<Vec<Vec<T>>>
+        where
+            T: Copy + Default + AddAssign + From<u8>,
+        {
+            /* Returns counts[i][s][k], the number of ways to place k mines on the cells from the
+            i-th assigned on from state s, counting from the last cell back */
+            let n = self.order.len();
+            let mut counts: Vec<Vec<Vec<T>>> = vec![Vec::new(); n + 1];
+            counts[n] = vec![vec![T::from(1)]];
+            for i in (0..n).rev() {
+                counts[i] = self.next[i]
+                    .iter()
+                    .map(|options| {
+                        let mut total = vec![T::default(); n - i + 1];
+                        for (is_mine, state) in options.iter().enumerate() {
+                            if let Some(state) = *state {
+                                for (mines, &count) in counts[i + 1][state].iter().enumerate() {
+                                    total[mines + is_mine] += count;
+                                }
+                            }
+                        }
+                        total
+                    })
+                    .collect();
+            }
+            counts
+        }
+    }
+
+    struct ComponentSampler {
+        /* Counts the consistent assignments of mines to a group of frontier cells, and draws
+        them uniformly, cell by cell along their AssignmentGraph */
+        // Cells in the order they are assigned
+        cells: Vec<Cell>,
+        // next[i][s] is the state each value of cells[i] leads to from state s, and
+        // counts[i][s][k] is the number of ways to place k mines on the cells from
+        // cells[i] on from state s
+        next: Vec<Vec<[Option<usize>; 2]>>,
+        counts: Vec<Vec<Vec<f64>>>,
+    }
+
+    impl ComponentSampler {
+        fn new(cells: Vec<Cell>, sentences: &[&Sentence]) -> Option<ComponentSampler> {
+            /* Returns None if counting the assignments takes more than SAMPLE_TALLY_LIMIT tallies */
+            let graph = AssignmentGraph::new(&cells, sentences, SAMPLE_TALLY_LIMIT)?;
+            Some(ComponentSampler {
+                cells: graph.order.iter().map(|&c| cells[c]).collect(),
+                counts: graph.count_back(),
+                next: graph.next,
+            })
+        }
+
+        fn solutions(&self) -> &[f64] {
+            /* Returns the number of consistent assignments placing each number of mines */
+            &self.counts[0][0]
+        }
+
+        fn sample<R: Rng + ?Sized>(&self, rng: &mut R, mut mines: usize, layout: &mut CellSet) {
+            /* Adds to the layout an assignment drawn uniformly among those placing `mines` mines,
+            choosing every cell in turn in proportion to the assignments it leaves */
+            let mut state = 0;
+            for (i, &cell) in self.cells.iter().enumerate() {
+                let weight = |is_mine: usize| match self.next[i][state][is_mine] {
+                    Some(next) if mines >= is_mine => self.counts[i + 1][next]
+                        .get(mines - is_mine)
+                        .cloned()
+                        .unwrap_or(0.0),
+                    _ => 0.0,
+                };
+                let (safe, mine) = (weight(0), weight(1));
+                let is_mine = rng.gen::<f64>() * (safe + mine) >= safe;
+                if is_mine {
+                    layout.insert(cell);
+                }
+                mines -= is_mine as usize;
+                state = self.next[i][state][is_mine as usize]
+                    .expect("an assignment drawn can always be completed");
+            }
+        }
+    }
+
+    pub struct LayoutSampler {
+        /* Draws layouts of the mines uniformly among those agreeing with the knowledge base */
+        known_mines: CellSet,
+        components: Vec<ComponentSampler>,
+        // Cells no sentence mentions, and the mines not found yet
+        other: Vec<Cell>,
+        remaining: usize,
+        // prefix[j][t] is proportional to the number of ways the first j groups hold t mines
+        prefix: Vec<Vec<f64>>,
+        // Number of mines in all of the groups
+        totals: WeightedIndex<f64>,
+    }
+
+    impl LayoutSampler {
+        fn new(
+            known_mines: CellSet,
+            components: Vec<ComponentSampler>,
+            other: Vec<Cell>,
+            remaining: usize,
+        ) -> Option<LayoutSampler> {
+            /* Weighs every number of mines t in the groups by the ways the groups can hold
+            them times the C(other, remaining - t) ways to place the rest among the other
+            cells. Returns None if no layout agrees with the knowledge base */
+            let mut prefix = vec![vec![1.0]];
+            for component in &components {
+                let total = convolve(prefix.last()?, component.solutions());
+                // Scaling every count alike keeps them in range without changing the draws
+                let largest = total.iter().cloned().fold(0.0, f64::max);
+                if largest <= 0.0 {
+                    return None;
+                }
+                prefix.push(total.iter().map(|&n| n / largest).collect());
+            }
+
+            let rest = rest_weights(prefix.last()?.len(), remaining, other.len());
+            let weights = prefix
+                .last()?
+                .iter()
+                .zip(&rest)
+                .map(|(&n, &weight)| n * weight);
+            let totals = WeightedIndex::new(weights).ok()?;
+
+            Some(LayoutSampler {
+                known_mines,
+                components,
+                other,
+                remaining,
+                prefix,
+                totals,
+            })
+        }
+
+        pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> CellSet {
+            /* Draws how many mines all of the groups hold, then how many of those each group
+            holds from the last one back, then one of the assignments of that many mines
+            to each group, and lastly where the rest of the mines are among the other cells */
+            let mut mines = self.known_mines.clone();
+            let total = self.totals.sample(rng);
+            let mut left = total;
+            for (j, component) in self.components.iter().enumerate().rev() {
+                let weights =
+                    component.solutions().iter().enumerate().map(|(k, &count)| {
+                        match left.checked_sub(k) {
+                            Some(rest) => count * self.prefix[j].get(rest).unwrap_or(&0.0),
+                            None => 0.0,
+                        }
+                    });
+                let k = WeightedIndex::new(weights)
+                    .expect("every total drawn can be split between the groups")
+                    .sample(rng);
+                component.sample(rng, k, &mut mines);
+                left -= k;
+            }
+            mines.extend(
+                self.other
+                    .choose_multiple(rng, self.remaining - total)
+                    .cloned(),
+            );
+            mines
+        }
+    }
+
+    fn rest_weights(totals: usize, remaining: usize, other_cells: usize) -> Vec<f64> {
+        /* Returns, for every number of mines t below `totals` held by the groups of frontier
+        cells, the C(other_cells, remaining - t) ways to place the rest of the mines among the
+        other cells, relative to the largest of them, or 0 if the rest do not fit */
+        let mut ln_factorials = vec![0.0; other_cells + 1];
+        for n in 1..=other_cells {
+            ln_factorials[n] = ln_factorials[n - 1] + (n as f64).ln();
+        }
+        let ln_weights: Vec<Option<f64>> = (0..totals)
+            .map(|t| {
+                let rest = remaining.checked_sub(t)?;
+                (rest <= other_cells).then(|| {
+                    ln_factorials[other_cells]
+                        - ln_factorials[rest]
+                        - ln_factorials[other_cells - rest]
+                })
+            })
+            .collect();
+        let largest = ln_weights
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        ln_weights
+            .iter()
+            .map(|ln_weight| ln_weight.map_or(0.0, |ln_weight| (ln_weight - largest).exp()))
+            .collect()
+    }
+
+    fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+        /* Returns the weights of the totals of two independent counts with the given weights */
+        let mut total = vec![0.0; a.len() + b.len() - 1];
+        for (i, &x) in a.iter().enumerate() {
+            for (j, &y) in b.iter().enumerate() {
+                total[i + j] += x * y;
+            }
+        }
+        total
+    }
+
+    pub fn frontier_probabilities(
+        components: &[FrontierComponent],
+        remaining: usize,
+        other_cells: usize,
+    ) -> Option<(Vec<Vec<f64>>, f64)> {
+        /* Returns the chance of being a mine of every cell of each component, and of each of
+        the `other_cells` cells that no sentence mentions, over all the equally likely boards
+        placing exactly `remaining` mines in a way every component allows.
+        A board whose components hold t mines between them can place the rest of the mines
+        in C(other_cells, remaining - t) ways. Returns None if there is no such board */
+
+        // Scaling the counts of a component scales every weight alike, and keeps them in range
+        let scaled: Vec<(Vec<f64>, Vec<Vec<f64>>)> = components
+            .iter()
+            .map(|component| {
+                let largest = component
+                    .solutions
+                    .iter()
+                    .cloned()
+                    .max()
+                    .unwrap_or(0)
+                    .max(1) as f64;
+                let solutions = component
+                    .solutions
+                    .iter()
+                    .map(|&n| n as f64 / largest)
+                    .collect();
+                let mine_counts = component
+                    .mine_counts
+                    .iter()
+                    .map(|counts| counts.iter().map(|&n| n as f64 / largest).collect())
+                    .collect();
+                (solutions, mine_counts)
+            })
+            .collect();
+
+        // Weight of the boards with t mines in the components, relative to the largest
+        let frontier_cells: usize = components
+            .iter()
+            .map(|component| component.cells.len())
+            .sum();
+        let weights = rest_weights(frontier_cells + 1, remaining, other_cells);
+        let weight = |t: usize| weights[t];
+
+        let all = scaled.iter().fold(vec![1.0], |total, (solutions, _)| {
+            convolve(&total, solutions)
+        });
+        let total: f64 = all.iter().enumerate().map(|(t, &n)| n * weight(t)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let other = match other_cells {
+            0 => 0.0,
+            _ => {
+                all.iter()
+                    .enumerate()
+                    .map(|(t, &n)| n * weight(t) * remaining.saturating_sub(t) as f64)
+                    .sum::<f64>()
+                    / other_cells as f64
+                    / total
+            }
+        };
+
+        let mut probabilities = Vec::new();
+        for (i, (_, mine_counts)) in scaled.iter().enumerate() {
+            let others = scaled
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .fold(vec![1.0], |total, (_, (solutions, _))| {
+                    convolve(&total, solutions)
+                });
+            // Weight of the boards where this component holds k mines, per assignment
+            let mut cells = vec![0.0; components[i].cells.len()];
+            for (k, counts) in mine_counts.iter().enumerate() {
+                let rest: f64 = others
+                    .iter()
+                    .enumerate()
+                    .map(|(t, &n)| n * weight(k + t))
+                    .sum();
+                for (c, &count) in counts.iter().enumerate() {
+                    cells[c] += count * rest;
+                }
+            }
+            probabilities.push(cells.iter().map(|&p| (p / total).clamp(0.0, 1.0)).collect());
+        }
+        Some((probabilities, other.clamp(0.0, 1.0)))
+    }
+
+    #[cfg(feature = "sat")]
+    pub fn sat_deductions(
+        cells: &[Cell],
+        sentences: &[&Sentence],
+        remaining: usize,
+        other_cells: usize,
+    ) -> Option<(Vec<Cell>, Vec<Cell>)> {
+        /* Asks a SAT solver, for every cell of a group of frontier cells, whether the cell
+        can be a mine and whether it can be safe, given the sentences about the group and
+        `remaining` mines shared between the group and `other_cells` cells outside of it.
+        Returns the cells that are always safe and the cells that are always mines,
+        or None if the sentences cannot all hold */
+        SatGroup::default().deductions(
+            cells,
+            sentences,
+            remaining.saturating_sub(other_cells),
+            remaining,
+        )
+    }
+
+    // A kept solver is built again from scratch once it has this many times the
+    // variables it was built with, most of them encoding sentences that have since shrunk
+    #[cfg(feature = "sat")]
+    const SAT_REBUILD_FACTOR: usize = 4;
+
+    #[cfg(feature = "sat")]
+    #[derive(Debug, Clone, Default)]
+    struct SatGroup {
+        /* A SAT solver for a group of frontier cells, kept from one pass of inference to the
+        next. Sentences and known cells only ever add clauses, so a later pass adds what is
+        new and keeps the clauses learnt so far */
+        solver: SatSolver,
+        // Variable standing for each cell being a mine
+        vars: HashMap<Cell, usize>,
+        // Sentences whose clauses have been added
+        encoded: Vec<Sentence>,
+        // Cells counted to bound the number of mines in the group, whether their safe cells
+        // are counted rather than their mines, and the outputs of the counter: output j is
+        // true when more than j of the cells are counted
+        counted: Vec<Cell>,
+        counts_safes: bool,
+        outputs: Vec<Lit>,
+        // Variables the solver had once first built
+        built_with: usize,
+        // Cells and bounds on their number of mines the solver was last asked about
+        last_run: Option<(Vec<Cell>, usize, usize)>,
+    }
+
+    #[cfg(feature = "sat")]
+    impl SatGroup {
+        fn lit(&mut self, cell: Cell) -> Lit {
+            let solver = &mut self.solver;
+            Lit::positive(*self.vars.entry(cell).or_insert_with(|| solver.new_var()))
+        }
+
+        fn fix(&mut self, known_mines: &CellSet, known_safes: &CellSet) {
+            /* Adds what has become known about the cells of the solver, or starts over
+            if the solver has grown too large */
+            if self.solver.num_vars() > SAT_REBUILD_FACTOR * self.built_with {
+                *self = SatGroup::default();
+            }
+            for (&cell, &var) in &self.vars {
+                if known_mines.contains(cell) {
+                    self.solver.add_clause(&[Lit::positive(var)]);
+                } else if known_safes.contains(cell) {
+                    self.solver.add_clause(&[Lit::negative(var)]);
+                }
+            }
+        }
+
+        fn deductions(
+            &mut self,
+            cells: &[Cell],
+            sentences: &[&Sentence],
+            min: usize,
+            max: usize,
+        ) -> Option<(Vec<Cell>, Vec<Cell>)> {
+            /* Returns the cells of the group that are always safe and those that are always
+            mines when the group holds between `min` and `max` mines, or None if the sentences
+            cannot all hold. Nothing is found again for a group whose sentences and bounds
+            have not changed since the last call */
+            if min > cells.len() {
+                return None;
+            }
+            // Counting the mines only pays when the bounds can rule out what the sentences allow
+            let (fewest, most) = mine_range(cells.len(), sentences);
+            let (min, max) = (min.max(fewest), max.min(most));
+            // Bounds on what is counted, and whether each of them binds
+            let n = cells.len();
+            let mines = (min, max, min > fewest, max < most);
+            let safes = (n - max, n - min, max < most, min > fewest);
+            let limit = |(low, high, low_binds, high_binds): (usize, usize, bool, bool)| {
+                if high_binds {
+                    high + 1
+                } else if low_binds {
+                    low
+                } else {
+                    0
+                }
+            };
+            // The safe cells are counted instead when that takes a shorter counter
+            let counts_safes = limit(safes) < limit(mines);
+            let counted_bounds = match counts_safes {
+                true => safes,
+                false => mines,
+            };
+            let (low, high, low_binds, high_binds) = counted_bounds;
+            let counts = low_binds || high_binds;
+            if counts
+                && (self.counted != cells
+                    || self.counts_safes != counts_safes
+                    || self.outputs.len() < limit(counted_bounds).min(n))
+            {
+                // Counters over other cells would only slow the solver down, so it starts over
+                let last_run = self.last_run.take();
+                *self = SatGroup {
+                    last_run,
+                    ..SatGroup::default()
+                };
+            }
+
+            let mut changed = self.last_run != Some((cells.to_vec(), min, max));
+            for &sentence in sentences {
+                if !self.encoded.contains(sentence) {
+                    let lits: Vec<Lit> = sentence.cells.iter().map(|cell| self.lit(cell)).collect();
+                    self.solver.between(&lits, sentence.min, sentence.max);
+                    self.encoded.push(sentence.clone());
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Some((Vec::new(), Vec::new()));
+            }
+            self.last_run = Some((cells.to_vec(), min, max));
+
+            let lits: Vec<Lit> = cells.iter().map(|&cell| self.lit(cell)).collect();
+            let mut bounds = Vec::new();
+            if counts {
+                if self.counted != cells {
+                    let counted: Vec<Lit> = match counts_safes {
+                        true => lits.iter().map(|&lit| !lit).collect(),
+                        false => lits.clone(),
+                    };
+                    self.outputs = self.solver.counter(&counted, limit(counted_bounds));
+                    self.counted = cells.to_vec();
+                    self.counts_safes = counts_safes;
+                }
+                if low_binds {
+                    bounds.push(self.outputs[low - 1]);
+                }
+                if high_binds {
+                    bounds.push(!self.outputs[high]);
+                }
+            }
+            if self.built_with == 0 {
+                self.built_with = self.solver.num_vars();
+            }
+
+            // Every model found shows a value each cell can take, so that value is not asked about
+            let mut can_be_mine = vec![false; cells.len()];
+            let mut can_be_safe = vec![false; cells.len()];
+            let record = |model: &[bool], can_be_mine: &mut [bool], can_be_safe: &mut [bool]| {
+                for (c, lit) in lits.iter().enumerate() {
+                    match model[lit.var()] {
+                        true => can_be_mine[c] = true,
+                        false => can_be_safe[c] = true,
+                    }
+                }
+            };
+            let model = self.solver.solve(&bounds)?;
+            record(&model, &mut can_be_mine, &mut can_be_safe);
+            for (c, &lit) in lits.iter().enumerate() {
+                for (shown, lit) in [(can_be_mine[c], lit), (can_be_safe[c], !lit)] {
+                    if shown {
+                        continue;
+                    }
+                    let mut assumptions = bounds.clone();
+                    assumptions.push(lit);
+                    if let Some(model) = self.solver.solve(&assumptions) {
+                        record(&model, &mut can_be_mine, &mut can_be_safe);
+                    }
+                }
+            }
+            Some((
+                (0..cells.len())
+                    .filter(|&c| !can_be_mine[c])
+                    .map(|c| cells[c])
+                    .collect(),
+                (0..cells.len())
+                    .filter(|&c| !can_be_safe[c])
+                    .map(|c| cells[c])
+                    .collect(),
+            ))
+        }
+    }
+
+    #[cfg(feature = "sat")]
+    fn mine_range(cells: usize, sentences: &[&Sentence]) -> (usize, usize) {
+        /* Returns bounds on the number of mines the sentences about a group of `cells` cells
+        allow: at least the mines of sentences sharing no cells, and at most those of the
+        cells each sentence adds to the ones before it */
+        let mut disjoint = match sentences.first() {
+            Some(first) => first.cells.clone(),
+            None => return (0, cells),
+        };
+        disjoint.clear();
+        let mut covered = disjoint.clone();
+        let (mut fewest, mut most) = (0, 0);
+        for sentence in sentences {
+            if sentence.cells.is_disjoint(&disjoint) {
+                fewest += sentence.min;
+                disjoint.union_with(&sentence.cells);
+            }
+            most += sentence.max.min(sentence.cells.difference(&covered).len());
+            covered.union_with(&sentence.cells);
+        }
+        (fewest, most + cells.saturating_sub(covered.len()))
+    }
+
+    pub fn restrict_mine_totals(
+        components: &mut [FrontierComponent],
+        remaining: usize,
+        other_cells: usize,
+    ) -> Vec<usize> {
+        /* Drops the assignments of each component that cannot be completed into a board
+        holding exactly `remaining` mines, the components sharing them with `other_cells`
+        cells that no sentence mentions. Returns the possible numbers of mines held by
+        all components together, which is empty if the knowledge is inconsistent */
+        let fits = |total: usize| total <= remaining && total + other_cells >= remaining;
+        for i in 0..components.len() {
+            let others = reachable_totals(components, Some(i));
+            components[i].retain_mine_totals(|mines| {
+                others
+                    .iter()
+                    .enumerate()
+                    .any(|(total, &reachable)| reachable && fits(total + mines))
+            });
+        }
+        reachable_totals(components, None)
+            .into_iter()
+            .enumerate()
+            .filter(|&(total, reachable)| reachable && fits(total))
+            .map(|(total, _)| total)
+            .collect()
+    }
+
+    fn gcd(a: i64, b: i64) -> i64 {
+        let (mut a, mut b) = (a.abs(), b.abs());
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct LinearSystem {
+        /* The exact sentences about a group of cells as linear equations over variables that
+        are 1 for a mine and 0 for a safe cell: the cells of a sentence add up to its count */
+        pub cells: Vec<Cell>,
+        // Coefficient of every cell in each equation, followed by its right-hand side
+        pub rows: Vec<Vec<i64>>,
+    }
+
+    impl LinearSystem {
+        pub fn new(cells: Vec<Cell>, sentences: &[&Sentence]) -> LinearSystem {
+            /* Builds the equations of the sentences with an exact number of mines */
+            let index: HashMap<Cell, usize> = cells
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| (*cell, i))
+                .collect();
+            let rows = sentences
+                .iter()
+                .filter(|sentence| sentence.is_exact())
+                .map(|sentence| {
+                    let mut row = vec![0; cells.len() + 1];
+                    for cell in &sentence.cells {
+                        row[index[&cell]] = 1;
+                    }
+                    row[cells.len()] = sentence.min as i64;
+                    row
+                })
+                .collect();
+            LinearSystem { cells, rows }
+        }
+
+        pub fn reduce(&mut self) {
+            /* Brings the equations to reduced row echelon form with integer arithmetic,
+            dividing each row by the common factor of its entries. A step that would
+            overflow is skipped, which leaves equations that still hold */
+            let mut rank = 0;
+            for column in 0..self.cells.len() {
+                let pivot = match (rank..self.rows.len()).find(|&r| self.rows[r][column] != 0) {
+                    Some(pivot) => pivot,
+                    None => continue,
+                };
+                self.rows.swap(rank, pivot);
+                for r in 0..self.rows.len() {
+                    if r != rank && self.rows[r][column] != 0 {
+                        if let Some(row) = self.eliminate(r, rank, column) {
+                            self.rows[r] = row;
+                        }
+                    }
+                }
+                rank += 1;
+            }
+        }
+
+        fn eliminate(&self, target: usize, pivot: usize, column: usize) -> Option<Vec<i64>> {
+            /* Returns the target row with the pivot row's multiple cancelling its entry in `column` */
+            let a = self.rows[pivot][column];
+            let b = self.rows[target][column];
+            let mut row = Vec::with_capacity(self.rows[target].len());
+            for (x, y) in self.rows[target].iter().zip(&self.rows[pivot]) {
+                row.push(x.checked_mul(a)?.checked_sub(y.checked_mul(b)?)?);
+            }
+            let divisor = row.iter().fold(0, |divisor, &x| gcd(divisor, x));
+            if divisor > 1 {
+                row.iter_mut().for_each(|x| *x /= divisor);
+            }
+            Some(row)
+        }
+
+        fn row_bounds(row: &[i64]) -> (i128, i128, i128) {
+            /* Returns the lowest and highest values the left-hand side of an equation can
+            take, followed by its right-hand side */
+            let (coefficients, rhs) = row.split_at(row.len() - 1);
+            let low = coefficients
+                .iter()
+                .filter(|&&a| a < 0)
+                .map(|&a| a as i128)
+                .sum();
+            let high = coefficients
+                .iter()
+                .filter(|&&a| a > 0)
+                .map(|&a| a as i128)
+                .sum();
+            (low, high, rhs[0] as i128)
+        }
+
+        pub fn is_consistent(&self) -> bool {
+            /* Returns false if some equation cannot be satisfied by any assignment */
+            self.rows.iter().all(|row| {
+                let (low, high, rhs) = LinearSystem::row_bounds(row);
+                low <= rhs && rhs <= high
+            })
+        }
+
+        fn cells_where(&self, is_mine: bool) -> Vec<Cell> {
+            /* Returns the cells that some equation forces to be a mine (or to be safe) */
+            let mut forced = vec![false; self.cells.len()];
+            for row in &self.rows {
+                let (low, high, rhs) = LinearSystem::row_bounds(row);
+                for (c, &a) in row[..self.cells.len()].iter().enumerate() {
+                    let a = a as i128;
+                    // Setting the cell to 1 (or to 0) would put the equation out of reach
+                    let mine_impossible = low + a.max(0) > rhs || high + a.min(0) < rhs;
+                    let safe_impossible = low - a.min(0) > rhs || high - a.max(0) < rhs;
+                    if a != 0
+                        && (if is_mine {
+                            safe_impossible
+                        } else {
+                            mine_impossible
+                        })
+                    {
+                        forced[c] = true;
+                    }
+                }
+            }
+            self.cells
+                .iter()
+                .zip(forced)
+                .filter(|(_, forced)| *forced)
+                .map(|(cell, _)| *cell)
+                .collect()
+        }
+
+        pub fn safes(&self) -> Vec<Cell> {
+            self.cells_where(false)
+        }
+
+        pub fn mines(&self) -> Vec<Cell> {
+            self.cells_where(true)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn seeded_boards_keep_their_layout() {
+            // Daily puzzles and bug reports rely on a seed always giving the same board
+            let board = Minesweeper::with_seed(9, 9, 10, 42).unwrap();
+            let expected = [
+                (1, 1),
+                (1, 7),
+                (2, 4),
+                (2, 7),
+                (6, 0),
+                (6, 5),
+                (6, 6),
+                (6, 8),
+                (7, 0),
+                (8, 5),
+            ];
+            assert_eq!(board.mines.iter().collect::<Vec<_>>(), expected);
+
+            let mut board = Minesweeper::lazy_with_seed(9, 9, 10, FirstClick::Opening, 42).unwrap();
+            board.place_mines((1, 1)).unwrap();
+            let expected = [
+                (1, 7),
+                (2, 4),
+                (2, 7),
+                (6, 0),
+                (6, 5),
+                (6, 6),
+                (6, 8),
+                (7, 0),
+                (7, 8),
+                (8, 5),
+            ];
+            assert_eq!(board.mines.iter().collect::<Vec<_>>(), expected);
+        }
+
+        fn seeded_session() -> GameSession {
+            /* A 9x9 game with mines at (1, 1), (1, 7), (2, 4), (2, 7), (6, 0), (6, 5), (6, 6),
+            (6, 8), (7, 0) and (8, 5) */
+            GameSession::new(Minesweeper::with_seed(9, 9, 10, 42).unwrap())
+        }
+
+        fn safe_cells(session: &GameSession) -> Vec<Cell> {
+            (0..9)
+                .flat_map(|i| (0..9).map(move |j| (i, j)))
+                .filter(|&cell| !session.game.mines.contains(cell))
+                .collect()
+        }
+
+        #[test]
+        fn sessions_are_won_by_revealing_every_safe_cell() {
+            let mut session = seeded_session();
+            assert_eq!(session.status(), GameStatus::NotStarted);
+
+            // Flags neither start the game nor let the flagged cell be opened
+            assert_eq!(session.flag((4, 2)).unwrap(), MoveResult::Flagged((4, 2)));
+            assert_eq!(session.status(), GameStatus::NotStarted);
+            assert_eq!(session.reveal((4, 2)).unwrap(), MoveResult::Ignored);
+            assert_eq!(
+                session.toggle_flag((4, 2)).unwrap(),
+                MoveResult::Unflagged((4, 2))
+            );
+
+            // No mine is next to (4, 2), so opening it cascades
+            match session.reveal((4, 2)).unwrap() {
+                MoveResult::Opened(opened) => assert!(opened.len() > 1),
+                result => panic!("unexpected {:?}", result),
+            }
+            assert_eq!(session.status(), GameStatus::Playing);
+            assert_eq!(session.reveal((4, 2)).unwrap(), MoveResult::Ignored);
+
+            for cell in safe_cells(&session) {
+                session.reveal(cell).unwrap();
+            }
+            assert_eq!(session.status(), GameStatus::Won);
+            assert!(session.is_over());
+            assert_eq!(session.flags, session.game.mines);
+            assert_eq!(session.reveal((1, 1)).unwrap(), MoveResult::Ignored);
+            assert_eq!(session.status(), GameStatus::Won);
+        }
+
+        #[test]
+        fn sessions_are_lost_on_a_mine_and_ignore_later_moves() {
+            let mut session = seeded_session();
+            session.reveal((4, 2)).unwrap();
+            assert_eq!(session.reveal((1, 1)).unwrap(), MoveResult::Lost((1, 1)));
+            assert_eq!(session.status(), GameStatus::Lost);
+            assert!(session.is_over());
+
+            let revealed = session.game.revealed.clone();
+            assert_eq!(session.reveal((0, 8)).unwrap(), MoveResult::Ignored);
+            assert_eq!(session.flag((1, 7)).unwrap(), MoveResult::Ignored);
+            assert_eq!(session.chord((3, 2)).unwrap(), MoveResult::Ignored);
+            assert_eq!(session.game.revealed, revealed);
+            assert!(session.flags.is_empty());
+            assert_eq!(session.status(), GameStatus::Lost);
+
+            // Cells off the board are still errors
+            assert_eq!(
+                session.reveal((9, 0)),
+                Err(MinesweeperError::OutOfBounds((9, 0)))
+            );
+        }
+
+        #[test]
+        fn sessions_requiring_flags_are_won_by_the_last_flag() {
+            let mut session = seeded_session();
+            session.require_flags = true;
+            for cell in safe_cells(&session) {
+                session.reveal(cell).unwrap();
+            }
+            assert!(session.game.won());
+            assert_eq!(session.status(), GameStatus::Playing);
+
+            let mines: Vec<Cell> = session.game.mines.iter().collect();
+            for &mine in &mines[1..] {
+                session.flag(mine).unwrap();
+            }
+            assert_eq!(session.status(), GameStatus::Playing);
+            assert_eq!(
+                session.toggle_flag(mines[0]).unwrap(),
+                MoveResult::Flagged(mines[0])
+            );
+            assert_eq!(session.status(), GameStatus::Won);
+        }
+
+        #[test]
+        fn chording_opens_the_neighbours_of_a_satisfied_number() {
+            // (0, 0) shows a 1 for the mine at (1, 1)
+            let mut session = seeded_session();
+            assert_eq!(session.chord((0, 0)).unwrap(), MoveResult::Ignored);
+            assert_eq!(
+                session.reveal((0, 0)).unwrap(),
+                MoveResult::Opened(vec![((0, 0), 1)])
+            );
+            session.flag((1, 1)).unwrap();
+            assert_eq!(
+                session.chord((0, 0)).unwrap(),
+                MoveResult::Opened(vec![((0, 1), 1), ((1, 0), 1)])
+            );
+            assert_eq!(session.status(), GameStatus::Playing);
+        }
+
+        #[test]
+        fn chording_with_a_wrong_flag_loses() {
+            let mut session = seeded_session();
+            session.reveal((0, 0)).unwrap();
+            session.flag((0, 1)).unwrap();
+            assert_eq!(session.chord((0, 0)).unwrap(), MoveResult::Lost((1, 1)));
+            assert_eq!(session.status(), GameStatus::Lost);
+        }
+
+        #[test]
+        fn chording_needs_as_many_flags_as_mines() {
+            let mut session = seeded_session();
+            session.reveal((0, 0)).unwrap();
+            assert_eq!(session.chord((0, 0)).unwrap(), MoveResult::Ignored);
+            session.flag((0, 1)).unwrap();
+            session.flag((1, 1)).unwrap();
+            assert_eq!(session.chord((0, 0)).unwrap(), MoveResult::Ignored);
+
+            // Nor can a hidden cell be chorded, even when its flags would match
+            assert_eq!(session.chord((2, 1)).unwrap(), MoveResult::Ignored);
+            assert_eq!(session.game.revealed.len(), 1);
+            assert_eq!(session.status(), GameStatus::Playing);
+        }
+
+        #[test]
+        fn simulations_do_not_depend_on_the_threads() {
+            let mut simulation = Simulation::new(9, 9, 10, 20);
+            simulation.seed = 5;
+            let new_ai = || MinesweeperAI::new(9, 9, 10);
+            simulation.threads = 1;
+            let reports = simulation.run(new_ai).unwrap();
+            assert_eq!(reports.len(), 20);
+            for threads in [3, 8, 32] {
+                simulation.threads = threads;
+                assert_eq!(simulation.run(new_ai).unwrap(), reports);
+            }
+            // Each report is that of its own game, whichever thread played it
+            let mut ai = new_ai().unwrap();
+            assert_eq!(simulation.play_game(13, &mut ai).unwrap(), reports[13]);
+        }
+
+        #[test]
+        fn cells_off_the_board_are_rejected() {
+            let mut set = CellSet::new(3, 3);
+            assert!(!set.insert((3, 0)));
+            assert!(set.is_empty());
+
+            let mut ai = MinesweeperAI::new(3, 3, 1).unwrap();
+            assert_eq!(
+                ai.mark_mine((0, 3)),
+                Err(MinesweeperError::OutOfBounds((0, 3)))
+            );
+            assert_eq!(
+                ai.mark_safe((5, 5)),
+                Err(MinesweeperError::OutOfBounds((5, 5)))
+            );
+
+            // A sentence about a larger board never reaches the knowledge base
+            let mut cells = CellSet::new(5, 5);
+            cells.insert((4, 4));
+            let foreign = Sentence::new(cells, 1);
+            assert!(!ai.knowledge.insert(foreign.clone()));
+            assert!(!ai.knowledge.contains(&foreign));
+            assert_eq!(ai.knowledge.sentences_with((4, 4)).count(), 0);
+            ai.knowledge.mark_mine((4, 4));
+            assert!(ai.knowledge.is_empty());
+        }
+
+        fn sentence(cells: &[Cell], min: usize, max: usize) -> Sentence {
+            let mut set = CellSet::new(4, 6);
+            set.extend(cells.iter().cloned());
+            Sentence::bounded(set, min, max)
+        }
+
+        fn hidden(columns: &[usize], mines: usize) -> Sentence {
+            /* The sentence of a number revealed above the given hidden cells of row 1 */
+            let cells: Vec<Cell> = columns.iter().map(|&j| (1, j)).collect();
+            sentence(&cells, mines, mines)
+        }
+
+        #[test]
+        fn overlap_of_a_subset() {
+            let overlap = Overlap::new(&hidden(&[0, 1, 2], 2), &hidden(&[0, 1], 1));
+            assert_eq!(overlap.shared, hidden(&[0, 1], 1));
+            assert_eq!(overlap.only_first, hidden(&[2], 1));
+            assert!(overlap.only_second.cells.is_empty());
+            assert_eq!(
+                overlap.sentences(),
+                vec![hidden(&[0, 1], 1), hidden(&[2], 1)]
+            );
+        }
+
+        #[test]
+        fn overlap_of_1_2_1() {
+            // Numbers 1, 2, 1 above columns 1, 2 and 3 of a row of five hidden cells
+            let (left, middle, right) = (
+                hidden(&[0, 1, 2], 1),
+                hidden(&[1, 2, 3], 2),
+                hidden(&[2, 3, 4], 1),
+            );
+            let overlap = Overlap::new(&left, &middle);
+            assert_eq!(
+                overlap.sentences(),
+                vec![hidden(&[1, 2], 1), hidden(&[0], 0), hidden(&[3], 1)]
+            );
+            let overlap = Overlap::new(&right, &middle);
+            assert_eq!(
+                overlap.sentences(),
+                vec![hidden(&[2, 3], 1), hidden(&[4], 0), hidden(&[1], 1)]
+            );
+        }
+
+        #[test]
+        fn overlap_of_1_2_2_1() {
+            // Numbers 1, 2, 2, 1 above columns 1 to 4 of a row of six hidden cells
+            let sentences = [
+                hidden(&[0, 1, 2], 1),
+                hidden(&[1, 2, 3], 2),
+                hidden(&[2, 3, 4], 2),
+                hidden(&[3, 4, 5], 1),
+            ];
+            let overlap = Overlap::new(&sentences[0], &sentences[1]);
+            assert_eq!(overlap.only_first, hidden(&[0], 0));
+            assert_eq!(overlap.only_second, hidden(&[3], 1));
+            let overlap = Overlap::new(&sentences[3], &sentences[2]);
+            assert_eq!(overlap.only_first, hidden(&[5], 0));
+            assert_eq!(overlap.only_second, hidden(&[2], 1));
+            // The two middle numbers share both mines
+            let overlap = Overlap::new(&sentences[1], &sentences[2]);
+            assert_eq!(overlap.shared, sentence(&[(1, 2), (1, 3)], 1, 2));
+            assert!(overlap.sentences().is_empty());
+
+            // Together the rules find every cell of the row
+            let mut ai = MinesweeperAI::new(4, 6, 5).unwrap();
+            for sentence in sentences {
+                ai.knowledge.insert(sentence);
+            }
+            ai.add_knowledge((3, 5), 0).unwrap();
+            assert_eq!(ai.known_mines.iter().collect::<Vec<_>>(), [(1, 2), (1, 3)]);
+            for j in [0, 1, 4, 5] {
+                assert!(ai.known_safes.contains((1, j)));
+            }
+            assert_eq!(ai.explain((1, 0)).unwrap().rule, Rule::Overlap);
+            assert_eq!(ai.explain((1, 1)).unwrap().rule, Rule::Subset);
+        }
+
+        #[test]
+        fn overlap_with_only_bounds() {
+            let overlap = Overlap::new(&hidden(&[0, 1, 2], 1), &hidden(&[1, 2, 3, 4], 2));
+            assert_eq!(overlap.shared, sentence(&[(1, 1), (1, 2)], 0, 1));
+            assert_eq!(overlap.only_first, sentence(&[(1, 0)], 0, 1));
+            assert_eq!(overlap.only_second, sentence(&[(1, 3), (1, 4)], 1, 2));
+            assert!(overlap.is_consistent());
+            assert!(overlap.sentences().is_empty());
+        }
+
+        #[test]
+        fn counts_needing_too_many_mines_are_rejected() {
+            // Together the two counts need five mines on a board with three
+            let mut ai = MinesweeperAI::new(3, 4, 3).unwrap();
+            ai.add_knowledge((2, 0), 2).unwrap();
+            let (moves_made, known_mines, known_safes) = (
+                ai.moves_made.clone(),
+                ai.known_mines.clone(),
+                ai.known_safes.clone(),
+            );
+            assert_eq!(
+                ai.add_knowledge((2, 3), 3),
+                Err(MinesweeperError::InconsistentKnowledge {
+                    cell: (2, 3),
+                    count: 3
+                })
+            );
+            assert_eq!(ai.moves_made, moves_made);
+            assert_eq!(ai.known_mines, known_mines);
+            assert_eq!(ai.known_safes, known_safes);
+
+            // So do more known mines than the board holds
+            let mut ai = MinesweeperAI::new(3, 4, 1).unwrap();
+            ai.mark_mine((0, 0)).unwrap();
+            ai.mark_mine((0, 1)).unwrap();
+            assert!(ai.add_knowledge((2, 3), 0).is_err());
+        }
+
+        #[test]
+        fn mines_left_are_explained_by_the_sentences_taken_out() {
+            // Both mines of the board are among (1, 0), (1, 1), (1, 4) and (1, 5),
+            // so every other cell is safe
+            let mut ai = MinesweeperAI::new(4, 6, 2).unwrap();
+            ai.knowledge.insert(hidden(&[0, 1], 1));
+            ai.knowledge.insert(hidden(&[4, 5], 1));
+            ai.add_knowledge((3, 3), 0).unwrap();
+            assert_eq!(ai.known_safes.len(), 20);
+
+            let deduction = ai.explain((3, 0)).unwrap();
+            assert_eq!(deduction.rule, Rule::GlobalCount);
+            let mut taken_out = deduction.premises[1..].to_vec();
+            taken_out.sort_by_key(|sentence| sentence.cells.iter().next());
+            assert_eq!(taken_out, [hidden(&[0, 1], 1), hidden(&[4, 5], 1)]);
+            assert!(deduction.premises[0].cells.contains((3, 0)));
+        }
+
+        #[test]
+        fn sentences_that_stop_holding_are_kept() {
+            // One sentence allowing no mine is given one, and another runs out of cells for its mine
+            let mut knowledge = KnowledgeBase::new(4, 6);
+            knowledge.insert(hidden(&[0, 1], 0));
+            knowledge.insert(hidden(&[3], 1));
+            knowledge.mark_mine((1, 0));
+            knowledge.mark_safe((1, 3));
+            assert_eq!(knowledge.len(), 2);
+            assert!(knowledge.iter().all(|sentence| !sentence.is_consistent()));
+        }
+
+        #[test]
+        fn row_reduction_solves_1_2_1() {
+            let sentences = [
+                hidden(&[0, 1, 2], 1),
+                hidden(&[1, 2, 3], 2),
+                hidden(&[2, 3, 4], 1),
+            ];
+            let sentences: Vec<&Sentence> = sentences.iter().collect();
+            let cells: Vec<Cell> = (0..5).map(|j| (1, j)).collect();
+            let mut system = LinearSystem::new(cells, &sentences);
+            assert!(system.safes().is_empty() && system.mines().is_empty());
+            system.reduce();
+            assert!(system.is_consistent());
+            assert_eq!(system.safes(), [(1, 0), (1, 4)]);
+            assert_eq!(system.mines(), [(1, 1), (1, 3)]);
+
+            // The middle cell follows once the others are marked
+            let mut ai = MinesweeperAI::new(4, 6, 5).unwrap();
+            ai.engine = InferenceEngine::Gaussian;
+            for sentence in &sentences {
+                ai.knowledge.insert((*sentence).clone());
+            }
+            ai.add_knowledge((3, 5), 0).unwrap();
+            assert!(ai.known_safes.contains((1, 2)));
+        }
+
+        #[test]
+        fn row_reduction_finds_contradictions() {
+            // The first two cells hold a mine, but the three cells together hold none
+            let sentences = [hidden(&[0, 1], 1), hidden(&[0, 1, 2], 0)];
+            let sentences: Vec<&Sentence> = sentences.iter().collect();
+            let cells: Vec<Cell> = (0..3).map(|j| (1, j)).collect();
+            let mut system = LinearSystem::new(cells, &sentences);
+            assert!(system.is_consistent());
+            system.reduce();
+            assert!(!system.is_consistent());
+        }
+
+        #[test]
+        fn row_reduction_combines_more_than_two_sentences() {
+            // The first three sentences add up to the first and fourth cells holding one
+            // mine, which leaves none for the fifth cell; the subset and overlap rules,
+            // comparing two sentences at a time, do not find it
+            let sentences = [
+                hidden(&[0, 1], 1),
+                hidden(&[1, 2], 1),
+                hidden(&[2, 3], 1),
+                hidden(&[0, 3, 4], 1),
+            ];
+            let mut ai = MinesweeperAI::new(4, 6, 5).unwrap();
+            for sentence in &sentences {
+                ai.knowledge.insert(sentence.clone());
+            }
+            ai.add_knowledge((3, 5), 0).unwrap();
+            assert!(!ai.known_safes.contains((1, 4)));
+
+            let sentences: Vec<&Sentence> = sentences.iter().collect();
+            let cells: Vec<Cell> = (0..5).map(|j| (1, j)).collect();
+            let mut system = LinearSystem::new(cells, &sentences);
+            system.reduce();
+            assert!(system.is_consistent());
+            assert_eq!(system.safes(), [(1, 4)]);
+            assert!(system.mines().is_empty());
+
+            let mut ai = MinesweeperAI::new(4, 6, 5).unwrap();
+            ai.engine = InferenceEngine::Gaussian;
+            for sentence in &sentences {
+                ai.knowledge.insert((*sentence).clone());
+            }
+            ai.add_knowledge((3, 5), 0).unwrap();
+            assert!(ai.known_safes.contains((1, 4)));
+            assert_eq!(ai.explain((1, 4)).unwrap().rule, Rule::Gaussian);
+        }
+
+        #[test]
+        fn row_reduction_skips_steps_that_overflow() {
+            // Eliminating the first cell from the second equation would overflow, so that
+            // equation is kept as it is; every equation still holds for a mine at (0, 0)
+            let mut system = LinearSystem {
+                cells: vec![(0, 0), (0, 1)],
+                rows: vec![vec![2, 1, 2], vec![i64::MAX, 1, i64::MAX]],
+            };
+            system.reduce();
+            for row in &system.rows {
+                assert_eq!(row[0], row[2]);
+            }
+            assert!(system.is_consistent());
+            assert_eq!(system.mines(), [(0, 0)]);
+        }
+
+        #[test]
+        fn enumeration_counts_every_assignment() {
+            // A two-row band, with one sentence only bounding its mines
+            let sentences = [
+                sentence(&[(0, 0), (0, 1), (1, 0), (1, 1)], 1, 1),
+                sentence(&[(0, 1), (0, 2), (1, 1), (1, 2)], 2, 2),
+                sentence(&[(0, 2), (0, 3), (1, 2), (1, 3)], 1, 3),
+                sentence(&[(1, 0), (1, 3)], 1, 1),
+            ];
+            let sentences: Vec<&Sentence> = sentences.iter().collect();
+            let cells: Vec<Cell> = (0..2).flat_map(|i| (0..4).map(move |j| (i, j))).collect();
+            let component = FrontierComponent::enumerate(cells.clone(), &sentences).unwrap();
+
+            let mut solutions = vec![0; cells.len() + 1];
+            let mut mine_counts = vec![vec![0; cells.len()]; cells.len() + 1];
+            for layout in 0..1u32 << cells.len() {
+                let is_mine =
+                    |cell: Cell| layout >> cells.iter().position(|&c| c == cell).unwrap() & 1 == 1;
+                if sentences.iter().all(|sentence| {
+                    let mines = sentence.cells.iter().filter(|&cell| is_mine(cell)).count();
+                    sentence.min <= mines && mines <= sentence.max
+                }) {
+                    let mines = layout.count_ones() as usize;
+                    solutions[mines] += 1;
+                    for (c, &cell) in cells.iter().enumerate() {
+                        mine_counts[mines][c] += is_mine(cell) as u128;
+                    }
+                }
+            }
+            assert_eq!(component.solutions, solutions);
+            assert_eq!(component.mine_counts, mine_counts);
+        }
+
+        #[cfg(feature = "sat")]
+        #[test]
+        fn sat_solves_groups_too_large_to_enumerate() {
+            // Every other cell of every other row opened, which ties the frontier together
+            let board = Minesweeper::with_seed(12, 12, 24, 4).unwrap();
+            let mut ais = [InferenceEngine::Enumeration, InferenceEngine::Sat].map(|engine| {
+                let mut ai = MinesweeperAI::new(12, 12, 24).unwrap();
+                ai.engine = engine;
+                ai
+            });
+            for ai in &mut ais {
+                for cell in (0..12)
+                    .step_by(2)
+                    .flat_map(|i| (0..12).step_by(2).map(move |j| (i, j)))
+                {
+                    if !board.mines.contains(cell) && !ai.moves_made.contains(cell) {
+                        ai.add_knowledge(cell, board.nearby_mines(cell).unwrap())
+                            .unwrap();
+                    }
+                }
+            }
+            let [enumeration, sat] = ais;
+            assert!(sat
+                .known_mines
+                .iter()
+                .all(|cell| board.mines.contains(cell)));
+            assert!(sat
+                .known_safes
+                .iter()
+                .all(|cell| !board.mines.contains(cell)));
+            assert!(enumeration.known_mines.is_subset(&sat.known_mines));
+            assert!(enumeration.known_safes.is_subset(&sat.known_safes));
+
+            // The solver kept from pass to pass leaves nothing for a new one to find
+            let groups = split_frontier(sat.knowledge.iter());
+            let (cells, sentences) = groups
+                .iter()
+                .find(|(cells, sentences)| {
+                    FrontierComponent::enumerate(cells.clone(), sentences).is_none()
+                })
+                .unwrap();
+            assert!(cells.len() <= ENUMERATION_LIMIT);
+            let remaining = 24 - sat.known_mines.len();
+            let other_cells = sat.global_sentence().cells.len() - cells.len();
+            assert_eq!(
+                sat_deductions(cells, sentences, remaining, other_cells),
+                Some((Vec::new(), Vec::new()))
+            );
+        }
+
+        #[test]
+        fn probabilities_count_every_layout() {
+            // Two groups of frontier cells that may each hold one or two mines, and four cells
+            // no number touches, which share the mines the groups leave over
+            let board = Minesweeper::with_seed(4, 5, 4, 2).unwrap();
+            let opened = [(0, 0), (0, 2), (3, 2), (3, 4)];
+            let mut ai = MinesweeperAI::new(4, 5, 4).unwrap();
+            for cell in opened {
+                ai.add_knowledge(cell, board.nearby_mines(cell).unwrap())
+                    .unwrap();
+            }
+            let groups = split_frontier(ai.knowledge.iter());
+            assert_eq!(groups.len(), 2);
+            assert_eq!(
+                groups.iter().map(|(cells, _)| cells.len()).sum::<usize>(),
+                12
+            );
+
+            let cells: Vec<Cell> = (0..4).flat_map(|i| (0..5).map(move |j| (i, j))).collect();
+            let mut layouts = 0;
+            let mut mine_counts = vec![0; cells.len()];
+            for layout in (0..1u32 << cells.len()).filter(|layout| layout.count_ones() == 4) {
+                let is_mine = |(i, j): Cell| layout >> (i * 5 + j) & 1 == 1;
+                let nearby = |(i, j): Cell| {
+                    cells
+                        .iter()
+                        .filter(|&&(k, l)| {
+                            k.abs_diff(i) <= 1 && l.abs_diff(j) <= 1 && is_mine((k, l))
+                        })
+                        .count()
+                };
+                if opened.iter().all(|&cell| {
+                    !is_mine(cell) && nearby(cell) == board.nearby_mines(cell).unwrap()
+                }) {
+                    layouts += 1;
+                    for (c, &cell) in cells.iter().enumerate() {
+                        mine_counts[c] += is_mine(cell) as usize;
+                    }
+                }
+            }
+
+            let probabilities = ai.mine_probabilities();
+            assert!(probabilities.exact);
+            for (c, &cell) in cells.iter().enumerate() {
+                if !opened.contains(&cell) {
+                    let expected = mine_counts[c] as f64 / layouts as f64;
+                    assert!(
+                        (probabilities.get(cell).unwrap() - expected).abs() < 1e-12,
+                        "{:?}: {} rather than {}",
+                        cell,
+                        probabilities.get(cell).unwrap(),
+                        expected
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn layouts_are_sampled_uniformly() {
+            // Two groups whose number of mines varies, and cells no sentence mentions
+            let mut ai = MinesweeperAI::new(4, 6, 5).unwrap();
+            let sentences = [
+                hidden(&[0, 1, 2], 1),
+                hidden(&[2, 3, 4], 2),
+                sentence(&[(3, 4), (3, 5)], 0, 1),
+            ];
+            for sentence in &sentences {
+                ai.knowledge.insert(sentence.clone());
+            }
+            let sampler = ai.layout_sampler().unwrap();
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let draws = 20000;
+            let mut mine_counts: HashMap<Cell, usize> = HashMap::new();
+            for _ in 0..draws {
+                let mines = sampler.sample(&mut rng);
+                assert_eq!(mines.len(), 5);
+                for sentence in &sentences {
+                    let held = sentence.cells.intersection(&mines).len();
+                    assert!(sentence.min <= held && held <= sentence.max);
+                }
+                for mine in mines.iter() {
+                    *mine_counts.entry(mine).or_insert(0) += 1;
+                }
+            }
+
+            // Each cell is drawn a mine about as often as the chances say
+            let probabilities = ai.mine_probabilities();
+            assert!(probabilities.exact);
+            for (&cell, &probability) in &probabilities.cells {
+                let frequency = *mine_counts.get(&cell).unwrap_or(&0) as f64 / draws as f64;
+                assert!(
+                    (frequency - probability).abs() < 0.015,
+                    "{:?}: drawn {} rather than {}",
+                    cell,
+                    frequency,
+                    probability
+                );
+            }
+        }
+
+        #[test]
+        fn guesses_are_sampled_safe() {
+            let mut ai = MinesweeperAI::new(4, 6, 5).unwrap();
+            ai.knowledge.insert(hidden(&[0, 1], 1));
+            let sampler = ai.sampler_with(Some((1, 0))).unwrap();
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            for _ in 0..100 {
+                let mines = sampler.sample(&mut rng);
+                assert!(!mines.contains((1, 0)) && mines.contains((1, 1)));
+                assert_eq!(mines.len(), 5);
+            }
+
+            // A cell that is a mine in every layout is never drawn safe
+            ai.knowledge.insert(hidden(&[0], 1));
+            assert!(ai.sampler_with(Some((1, 0))).is_none());
+            assert!(ai.sampler_with(Some((1, 1))).is_some());
+        }
     }
 }
@@ -1,37 +1,103 @@
 use ggez::event::{self, EventHandler, MouseButton};
 use ggez::graphics::{self, Color, DrawMode, Image, Mesh, PxScale, Rect, Text, TextFragment};
+use ggez::input::keyboard::{KeyCode, KeyInput};
 use ggez::*;
 use rust_ai_minesweeper::game_logic::*;
-use std::collections::HashSet;
+use std::time::Duration;
 
 const HEIGHT: usize = 8;
 const WIDTH: usize = 8;
 const NUM_MINES: usize = 8;
 const TILE_SIZE: f32 = 50.0;
+// Work the AI may spend looking ahead before a guess, short enough to keep the window responsive
+const GUESS_BUDGET: SearchBudget = SearchBudget {
+    iterations: 2000,
+    time: Duration::from_millis(250),
+    depth: usize::MAX,
+};
+
+fn to_game_error(error: MinesweeperError) -> GameError {
+    GameError::CustomError(error.to_string())
+}
+
+fn new_ai(
+    height: usize,
+    width: usize,
+    num_of_mines: usize,
+) -> Result<Box<dyn Solver>, MinesweeperError> {
+    /* Builds the solver behind the "AI Move" button */
+    let mut ai = MinesweeperAI::new(height, width, num_of_mines)?;
+    ai.engine = InferenceEngine::Enumeration;
+    ai.guess_policy = GuessPolicy::MonteCarlo(GUESS_BUDGET);
+    Ok(Box::new(ai))
+}
+
+fn heat_color(probability: f64) -> Color {
+    /* Shades a hidden tile from green for a safe cell to red for a certain mine */
+    let p = probability as f32;
+    Color::new(0.3 + 0.6 * p, 0.8 - 0.6 * p, 0.3, 1.0)
+}
+
+fn cell_at(x: f32, y: f32) -> Option<(usize, usize)> {
+    /* Returns the board cell under the given window coordinates, if any */
+    let px_height = HEIGHT as f32 * TILE_SIZE;
+    let px_width = WIDTH as f32 * TILE_SIZE;
+    if x >= 0.0 && x < px_width && y >= 0.0 && y < px_height {
+        Some(((y / TILE_SIZE) as usize, (x / TILE_SIZE) as usize))
+    } else {
+        None
+    }
+}
 
 struct State {
-    game: Minesweeper,
-    ai: MinesweeperAI,
-    revealed: HashSet<(usize, usize)>,
-    flags: HashSet<(usize, usize)>,
-    lost: bool,
+    session: GameSession,
+    ai: Box<dyn Solver>,
     instructions: bool,
     flag_image: Image,
     mine_image: Image,
+    // Whether hidden tiles are tinted by the AI's chance of a mine, and labelled with it
+    heatmap: bool,
+    percentages: bool,
+    // Chances of a mine for the current knowledge of the AI, worked out once per change to it
+    // while the heatmap is on, so that drawing a frame never has to
+    probabilities: Option<MineProbabilities>,
 }
 
 impl State {
     pub fn new(ctx: &mut Context, height: usize, width: usize, num_of_mines: usize) -> Self {
         Self {
-            game: Minesweeper::new(height, width, num_of_mines),
-            ai: MinesweeperAI::new(height, width),
-            revealed: HashSet::new(),
-            flags: HashSet::new(),
-            lost: false,
+            session: GameSession::new(
+                Minesweeper::lazy(height, width, num_of_mines, FirstClick::Opening).unwrap(),
+            ),
+            ai: new_ai(height, width, num_of_mines).unwrap(),
             instructions: false,
             flag_image: Image::from_path(ctx, "/flag.png").unwrap(),
             mine_image: Image::from_path(ctx, "/mine.png").unwrap(),
+            heatmap: false,
+            percentages: false,
+            probabilities: None,
+        }
+    }
+
+    fn learn(&mut self, result: MoveResult) -> GameResult {
+        /* Passes every cell opened by a move on to the AI */
+        if let MoveResult::Opened(opened) = result {
+            // Drop the old chances first, so a failed observation cannot leave them behind
+            self.probabilities = None;
+            for (cell, count) in opened {
+                self.ai.observe(cell, count).map_err(to_game_error)?;
+            }
+            self.refresh_probabilities();
         }
+        Ok(())
+    }
+
+    fn refresh_probabilities(&mut self) {
+        /* Works out the chances shown by the heatmap, or forgets them while it is off */
+        self.probabilities = match self.heatmap {
+            true => self.ai.mine_probabilities(),
+            false => None,
+        };
     }
 }
 
@@ -42,6 +108,9 @@ impl EventHandler for State {
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         let mut canvas = graphics::Canvas::from_frame(ctx, graphics::Color::BLACK);
+        let known_mines = self.ai.flagged_mines();
+        let game = &self.session.game;
+        let lost = self.session.status() == GameStatus::Lost;
 
         // TODO: add instructions before drawing board
 
@@ -61,24 +130,66 @@ impl EventHandler for State {
                 )?;
                 canvas.draw(&outer_rect, graphics::DrawParam::default());
 
-                // Draw the inner rectangle
+                // Draw the inner rectangle, tinted by the AI's chance of a mine if the heatmap is on
+                let probability = match &self.probabilities {
+                    Some(probabilities)
+                        if self.heatmap
+                            && !self.session.is_over()
+                            && !game.revealed.contains((i, j)) =>
+                    {
+                        probabilities.get((i, j))
+                    }
+                    _ => None,
+                };
+                let inner = Rect::new(
+                    x + margin,
+                    y + margin,
+                    TILE_SIZE - margin * 2.0,
+                    TILE_SIZE - margin * 2.0,
+                );
                 let inner_rect = Mesh::new_rectangle(
                     ctx,
                     DrawMode::fill(),
-                    Rect::new(
-                        x + margin,
-                        y + margin,
-                        TILE_SIZE - margin * 2.0,
-                        TILE_SIZE - margin * 2.0,
-                    ),
-                    Color::from_rgb(125, 125, 125),
+                    inner,
+                    probability.map_or(Color::from_rgb(125, 125, 125), heat_color),
                 )?;
                 canvas.draw(&inner_rect, graphics::DrawParam::default());
 
+                // Highlight the cells the AI knows to be safe or mines
+                if let Some(probability) = probability {
+                    let highlight = if known_mines.contains((i, j)) {
+                        Some(Color::RED)
+                    } else if probability == 0.0 {
+                        Some(Color::GREEN)
+                    } else {
+                        None
+                    };
+                    if let Some(highlight) = highlight {
+                        let outline =
+                            Mesh::new_rectangle(ctx, DrawMode::stroke(3.0), inner, highlight)?;
+                        canvas.draw(&outline, graphics::DrawParam::default());
+                    }
+                    if self.percentages && !self.session.flags.contains((i, j)) {
+                        let text = Text::new(TextFragment {
+                            text: format!("{:.0}%", probability * 100.0),
+                            color: Some(Color::BLACK),
+                            font: Some("LiberationMono-Regular".into()),
+                            scale: Some(PxScale::from(16.0)),
+                        });
+                        canvas.draw(
+                            &text,
+                            graphics::DrawParam::default().dest([x + 8.0, y + 17.0]),
+                        );
+                    }
+                }
+
                 // Draw number
-                if self.revealed.contains(&(i, j)) {
+                if game.revealed.contains((i, j)) {
                     let text = Text::new(TextFragment {
-                        text: self.game.nearby_mines((i, j)).to_string(),
+                        text: game
+                            .nearby_mines((i, j))
+                            .map_err(to_game_error)?
+                            .to_string(),
                         color: Some(Color::BLACK),
                         font: Some("LiberationMono-Regular".into()),
                         scale: Some(PxScale::from(30.0)),
@@ -89,7 +200,7 @@ impl EventHandler for State {
                     );
 
                 // Draw flags
-                } else if self.flags.contains(&(i, j)) {
+                } else if self.session.flags.contains((i, j)) {
                     // let text = Text::new(TextFragment {
                     //     text: "F".to_string(),
                     //     color: Some(Color::BLACK),
@@ -108,7 +219,7 @@ impl EventHandler for State {
                     );
 
                 // Draw mines
-                } else if self.game.is_mine((i, j)) && self.lost {
+                } else if lost && game.is_mine((i, j)).map_err(to_game_error)? {
                     // let text = Text::new(TextFragment {
                     //     text: "M".to_string(),
                     //     color: Some(Color::BLACK),
@@ -179,19 +290,29 @@ impl EventHandler for State {
             graphics::DrawParam::default().dest([x_text, y_text]),
         );
 
+        // Draw the keys toggling the heatmap
+        let keys_text = Text::new(TextFragment {
+            text: "H: heatmap\nP: percentages".to_string(),
+            color: Some(Color::WHITE),
+            font: Some("LiberationMono-Regular".into()),
+            scale: Some(PxScale::from(20.0)),
+        });
+        canvas.draw(
+            &keys_text,
+            graphics::DrawParam::default().dest([x_reset_button, y_reset_button + 75.0]),
+        );
+
         // Draw winner or loser text
-        if self.lost {
+        if lost {
             let mut text = graphics::Text::new("Loser!");
             text.set_scale(200.0);
             let dest_point = [125.0, 400.0];
             canvas.draw(&text, graphics::DrawParam::default().dest(dest_point));
-        } else {
-            if self.game.mines == self.flags {
-                let mut text = graphics::Text::new("Winner!");
-                text.set_scale(200.0);
-                let dest_point = [75.0, 400.0];
-                canvas.draw(&text, graphics::DrawParam::default().dest(dest_point));
-            }
+        } else if self.session.status() == GameStatus::Won {
+            let mut text = graphics::Text::new("Winner!");
+            text.set_scale(200.0);
+            let dest_point = [75.0, 400.0];
+            canvas.draw(&text, graphics::DrawParam::default().dest(dest_point));
         }
 
         canvas.finish(ctx)?;
@@ -200,70 +321,86 @@ impl EventHandler for State {
 
     fn mouse_button_down_event(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         button: MouseButton,
         x: f32,
         y: f32,
     ) -> GameResult {
-        if button == MouseButton::Left {
-            let mut mv: Option<(usize, usize)> = None;
-
-            let px_height = HEIGHT as f32 * TILE_SIZE;
-            let px_width = WIDTH as f32 * TILE_SIZE;
+        // Middle click, or holding both buttons down together, chords a numbered cell
+        let other_button = match button {
+            MouseButton::Left => Some(MouseButton::Right),
+            MouseButton::Right => Some(MouseButton::Left),
+            _ => None,
+        };
+        if button == MouseButton::Middle
+            || other_button.is_some_and(|other| ctx.mouse.button_pressed(other))
+        {
+            if let Some(cell) = cell_at(x, y) {
+                let result = self.session.chord(cell).map_err(to_game_error)?;
+                self.learn(result)?;
+            }
+            return Ok(());
+        }
 
+        if button == MouseButton::Left {
             // human player made the move
-            if x >= 0.0 && x <= px_height && y >= 0.0 && y <= px_width {
-                let col = (x / TILE_SIZE) as usize;
-                let row = (y / TILE_SIZE) as usize;
-                if !self.flags.contains(&(row, col)) && !self.revealed.contains(&(row, col)) {
-                    mv = Some((row, col));
-                }
-            }
+            let mut mv: Option<(usize, usize)> = cell_at(x, y);
 
             // AI Move button clicked
-            if x >= 450.0 && x <= 600.0 && y >= 50.0 && y <= 100.0 && !self.lost {
-                if let Some(ai_move) = self
-                    .ai
-                    .make_safe_move()
-                    .or_else(|| self.ai.make_random_move())
-                {
-                    mv = Some(ai_move);
+            if (450.0..=600.0).contains(&x)
+                && (50.0..=100.0).contains(&y)
+                && !self.session.is_over()
+            {
+                if let Some(ai_move) = self.ai.next_move() {
+                    mv = Some(ai_move.cell());
                 } else {
-                    self.flags = self.ai.known_mines.clone();
+                    for mine in &self.ai.flagged_mines() {
+                        self.session.flag(mine).map_err(to_game_error)?;
+                    }
                 }
             }
 
             // Reset button clicked
-            if x >= 450.0 && x <= 600.0 && y >= 125.0 && y <= 175.0 {
-                self.revealed = HashSet::new();
-                self.flags = HashSet::new();
-                self.lost = false;
-                self.game = Minesweeper::new(HEIGHT, WIDTH, NUM_MINES);
-                self.ai = MinesweeperAI::new(HEIGHT, WIDTH);
+            if (450.0..=600.0).contains(&x) && (125.0..=175.0).contains(&y) {
+                self.session = GameSession::new(
+                    Minesweeper::lazy(HEIGHT, WIDTH, NUM_MINES, FirstClick::Opening)
+                        .map_err(to_game_error)?,
+                );
+                self.ai = new_ai(HEIGHT, WIDTH, NUM_MINES).map_err(to_game_error)?;
+                self.refresh_probabilities();
                 self.instructions = true;
                 return Ok(());
             }
 
             // Make move and update knowledge
             if let Some(mv) = mv {
-                if self.game.is_mine(mv) {
-                    self.lost = true;
-                } else {
-                    self.revealed.insert(mv);
-                    self.ai.add_knowledge(mv, self.game.nearby_mines(mv))
-                }
+                // Opening a zero cell cascades, and the AI learns about every opened cell
+                let result = self.session.reveal(mv).map_err(to_game_error)?;
+                self.learn(result)?;
             }
         }
         if button == MouseButton::Right {
-            let col = (x / TILE_SIZE) as usize;
-            let row = (y / TILE_SIZE) as usize;
-            if row < HEIGHT && col < WIDTH {
-                if self.game.is_mine((row, col)) {
-                    self.flags.insert((row, col));
-                } else {
-                    self.lost = true;
-                }
+            if let Some(cell) = cell_at(x, y) {
+                self.session.toggle_flag(cell).map_err(to_game_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        input: KeyInput,
+        _repeated: bool,
+    ) -> GameResult {
+        match input.keycode {
+            Some(KeyCode::H) => {
+                self.heatmap = !self.heatmap;
+                self.refresh_probabilities();
             }
+            Some(KeyCode::P) => self.percentages = !self.percentages,
+            Some(KeyCode::Escape) => ctx.request_quit(),
+            _ => {}
         }
         Ok(())
     }
@@ -0,0 +1,676 @@
+/*
+A small CDCL SAT solver, with cardinality constraints encoded as sequential counters
+and totalizers
+*/
+
+use std::ops::Not;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn positive(var: usize) -> Lit {
+        Lit(var as u32 * 2)
+    }
+
+    pub fn negative(var: usize) -> Lit {
+        Lit(var as u32 * 2 + 1)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 / 2) as usize
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+fn value(assignment: &[Option<bool>], lit: Lit) -> Option<bool> {
+    assignment[lit.var()].map(|value| value != lit.is_negative())
+}
+
+#[derive(Debug, Clone, Default)]
+struct VarOrder {
+    /* Variables in a binary heap, most active first. Assigned variables are only taken
+    out when they come up, and put back once they are unassigned */
+    heap: Vec<usize>,
+    // Index of each variable in the heap, None while it is not in it
+    position: Vec<Option<usize>>,
+}
+
+impl VarOrder {
+    fn push(&mut self, var: usize, activity: &[f64]) {
+        if var >= self.position.len() {
+            self.position.resize(var + 1, None);
+        }
+        if self.position[var].is_none() {
+            self.position[var] = Some(self.heap.len());
+            self.heap.push(var);
+            self.sift_up(self.heap.len() - 1, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let last = self.heap.pop()?;
+        if self.heap.is_empty() {
+            self.position[last] = None;
+            return Some(last);
+        }
+        let top = std::mem::replace(&mut self.heap[0], last);
+        self.position[top] = None;
+        self.position[last] = Some(0);
+        self.sift_down(0, activity);
+        Some(top)
+    }
+
+    fn raise(&mut self, var: usize, activity: &[f64]) {
+        /* Moves a variable whose activity grew towards the top */
+        if let Some(Some(i)) = self.position.get(var) {
+            self.sift_up(*i, activity);
+        }
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.position[self.heap[i]] = Some(i);
+        self.position[self.heap[j]] = Some(j);
+    }
+
+    fn sift_up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[i]] <= activity[self.heap[parent]] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len()
+                    && activity[self.heap[child]] > activity[self.heap[largest]]
+                {
+                    largest = child;
+                }
+            }
+            if largest == i {
+                break;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SatSolver {
+    /* Clauses are only ever added, and clauses learnt from conflicts are kept,
+    so repeated calls to `solve` with different assumptions get faster */
+    clauses: Vec<Vec<Lit>>,
+    // Clauses watching each literal, by literal index; the first two literals are watched
+    watches: Vec<Vec<usize>>,
+    assignment: Vec<Option<bool>>,
+    level: Vec<usize>,
+    // Clause that implied each assigned variable, None for decisions
+    reason: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    // Length of the trail at the start of each decision level
+    trail_lim: Vec<usize>,
+    // Literals on the trail whose consequences have been propagated
+    propagated: usize,
+    activity: Vec<f64>,
+    bump: f64,
+    // Value each variable last had, which decisions try again first
+    phase: Vec<bool>,
+    // Variables added by the cardinality encodings, which are never decided
+    auxiliary: Vec<bool>,
+    // Unassigned variables by activity, to pick decisions from
+    order: VarOrder,
+    // Set once the clauses are found unsatisfiable whatever the assumptions
+    unsat: bool,
+}
+
+impl SatSolver {
+    pub fn new() -> SatSolver {
+        SatSolver {
+            bump: 1.0,
+            ..SatSolver::default()
+        }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.assignment.len()
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        let var = self.new_auxiliary();
+        self.auxiliary[var] = false;
+        self.order.push(var, &self.activity);
+        var
+    }
+
+    fn new_auxiliary(&mut self) -> usize {
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.assignment.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.activity.push(0.0);
+        self.phase.push(false);
+        self.auxiliary.push(true);
+        self.assignment.len() - 1
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        self.assignment[lit.var()] = Some(!lit.is_negative());
+        self.level[lit.var()] = self.decision_level();
+        self.reason[lit.var()] = reason;
+        self.trail.push(lit);
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        /* Undoes every assignment made above the given decision level */
+        if self.decision_level() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for lit in self.trail.drain(start..) {
+            self.phase[lit.var()] = !lit.is_negative();
+            self.assignment[lit.var()] = None;
+            self.reason[lit.var()] = None;
+            if !self.auxiliary[lit.var()] {
+                self.order.push(lit.var(), &self.activity);
+            }
+        }
+        self.trail_lim.truncate(level);
+        self.propagated = start;
+    }
+
+    fn watch(&mut self, clause: Vec<Lit>) -> usize {
+        let id = self.clauses.len();
+        self.watches[clause[0].index()].push(id);
+        self.watches[clause[1].index()].push(id);
+        self.clauses.push(clause);
+        id
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        /* Adds the clause satisfied when at least one of the literals is true */
+        self.cancel_until(0);
+        if self.unsat {
+            return;
+        }
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            match value(&self.assignment, lit) {
+                // Already satisfied for good, or a literal that can never help
+                Some(true) => return,
+                Some(false) => {}
+                None if clause.contains(&!lit) => return,
+                None if !clause.contains(&lit) => clause.push(lit),
+                None => {}
+            }
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.enqueue(clause[0], None);
+                self.unsat = self.propagate().is_some();
+            }
+            _ => {
+                self.watch(clause);
+            }
+        }
+    }
+
+    pub fn at_most(&mut self, lits: &[Lit], k: usize) {
+        /* Adds clauses allowing at most `k` of the literals to be true, with a sequential
+        counter: counter[i][j] is implied when more than j of the first i + 1 literals are true */
+        let n = lits.len();
+        if k >= n {
+            return;
+        }
+        if k == 0 {
+            for &lit in lits {
+                self.add_clause(&[!lit]);
+            }
+            return;
+        }
+        let counter: Vec<Vec<Lit>> = (0..n - 1)
+            .map(|_| {
+                (0..k)
+                    .map(|_| Lit::positive(self.new_auxiliary()))
+                    .collect()
+            })
+            .collect();
+        self.add_clause(&[!lits[0], counter[0][0]]);
+        for &later in &counter[0][1..] {
+            self.add_clause(&[!later]);
+        }
+        for i in 1..n - 1 {
+            self.add_clause(&[!lits[i], counter[i][0]]);
+            self.add_clause(&[!counter[i - 1][0], counter[i][0]]);
+            for j in 1..k {
+                self.add_clause(&[!lits[i], !counter[i - 1][j - 1], counter[i][j]]);
+                self.add_clause(&[!counter[i - 1][j], counter[i][j]]);
+            }
+            self.add_clause(&[!lits[i], !counter[i - 1][k - 1]]);
+        }
+        self.add_clause(&[!lits[n - 1], !counter[n - 2][k - 1]]);
+    }
+
+    pub fn at_least(&mut self, lits: &[Lit], k: usize) {
+        /* Adds clauses requiring at least `k` of the literals to be true */
+        if k > lits.len() {
+            self.add_clause(&[]);
+            return;
+        }
+        let negated: Vec<Lit> = lits.iter().map(|&lit| !lit).collect();
+        self.at_most(&negated, lits.len() - k);
+    }
+
+    pub fn between(&mut self, lits: &[Lit], min: usize, max: usize) {
+        self.at_least(lits, min);
+        self.at_most(lits, max);
+    }
+
+    pub fn counter(&mut self, lits: &[Lit], limit: usize) -> Vec<Lit> {
+        /* Adds a totalizer counting the true literals, and returns its outputs: output j is
+        true exactly when more than j of the literals are true, for j below `limit`. Bounds
+        on the count are then set by assuming outputs, so they can change between calls
+        to `solve`. Takes O(n log n) variables for n literals */
+        if lits.len() <= 1 {
+            return lits[..limit.min(lits.len())].to_vec();
+        }
+        let (left, right) = lits.split_at(lits.len() / 2);
+        let (left, right) = (self.counter(left, limit), self.counter(right, limit));
+        let outputs: Vec<Lit> = (0..limit.min(lits.len()))
+            .map(|_| Lit::positive(self.new_auxiliary()))
+            .collect();
+        // at_least(side, i) is true when more than i - 1 literals on a side are true
+        let at_least = |side: &[Lit], i: usize| match i {
+            0 => None,
+            i => side.get(i - 1).copied(),
+        };
+        for i in 0..=left.len() {
+            for j in 0..=right.len() {
+                // More than i - 1 on the left and j - 1 on the right make more than i + j - 1
+                if i + j > 0 {
+                    let mut clause: Vec<Lit> = [at_least(&left, i), at_least(&right, j)]
+                        .into_iter()
+                        .flatten()
+                        .map(|lit| !lit)
+                        .collect();
+                    clause.push(outputs[(i + j).min(outputs.len()) - 1]);
+                    self.add_clause(&clause);
+                }
+            }
+        }
+        for (t, &output) in outputs.iter().enumerate() {
+            // At most i on the left and t - i on the right make at most t
+            for i in 0..=t {
+                let mut clause = vec![!output];
+                if i < left.len() {
+                    clause.push(left[i]);
+                } else if i < lits.len() / 2 {
+                    continue;
+                }
+                if t - i < right.len() {
+                    clause.push(right[t - i]);
+                } else if t - i < lits.len() - lits.len() / 2 {
+                    continue;
+                }
+                self.add_clause(&clause);
+            }
+        }
+        outputs
+    }
+
+    fn propagate(&mut self) -> Option<usize> {
+        /* Assigns every literal implied by a clause with a single literal left unassigned.
+        Returns the clause found false, if any */
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watching = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watching.len() {
+                let id = watching[i];
+                let clause = &mut self.clauses[id];
+                // Keep the false literal second, so the first one is the other watch
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                if value(&self.assignment, clause[0]) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                // Look for another literal that is not false to watch instead
+                if let Some(k) =
+                    (2..clause.len()).find(|&k| value(&self.assignment, clause[k]) != Some(false))
+                {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(id);
+                    watching.swap_remove(i);
+                    continue;
+                }
+                let first = clause[0];
+                if value(&self.assignment, first) == Some(false) {
+                    conflict = Some(id);
+                    break;
+                }
+                self.enqueue(first, Some(id));
+                i += 1;
+            }
+            self.watches[false_lit.index()] = watching;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        /* Learns a clause from a conflict by resolving it with the reasons of the literals
+        assigned at the current level, up to the first unique implication point.
+        Returns the clause and the level to jump back to */
+        let mut seen = vec![false; self.num_vars()];
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut implied: Option<Lit> = None;
+        loop {
+            // The first literal of a reason is the one it implied
+            let skip = implied.is_some() as usize;
+            for k in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if seen[var] || self.level[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.activity[var] += self.bump;
+                self.order.raise(var, &self.activity);
+                if self.level[var] == self.decision_level() {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            // Step back to the latest literal of the current level taking part in the conflict
+            loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            seen[lit.var()] = false;
+            implied = Some(lit);
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            clause = self.reason[lit.var()].expect("implied literal without a reason");
+        }
+        learnt[0] = !implied.expect("conflict without literals at the current level");
+
+        // Watch the literal of the highest level after the implied one
+        let mut backjump = 0;
+        if learnt.len() > 1 {
+            let highest = (1..learnt.len())
+                .max_by_key(|&k| self.level[learnt[k].var()])
+                .unwrap_or(1);
+            learnt.swap(1, highest);
+            backjump = self.level[learnt[1].var()];
+        }
+        (learnt, backjump)
+    }
+
+    fn decay(&mut self) {
+        /* Makes recent conflicts weigh more than older ones when picking decisions */
+        self.bump *= 1.05;
+        if self.bump > 1e100 {
+            self.activity
+                .iter_mut()
+                .for_each(|activity| *activity *= 1e-100);
+            self.bump *= 1e-100;
+        }
+    }
+
+    fn pick_branch(&mut self) -> Option<usize> {
+        /* Returns the unassigned variable most involved in recent conflicts */
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.assignment[var].is_none() {
+                return Some(var);
+            }
+        }
+        None
+    }
+
+    pub fn solve(&mut self, assumptions: &[Lit]) -> Option<Vec<bool>> {
+        /* Looks for an assignment satisfying every clause with all of the assumptions true.
+        Returns the value of every variable, or None if there is no such assignment */
+        self.cancel_until(0);
+        if self.unsat {
+            return None;
+        }
+        let model = loop {
+            if let Some(conflict) = self.propagate() {
+                if self.decision_level() == 0 {
+                    self.unsat = true;
+                    break None;
+                }
+                let (learnt, backjump) = self.analyze(conflict);
+                self.cancel_until(backjump);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let first = learnt[0];
+                    let id = self.watch(learnt);
+                    self.enqueue(first, Some(id));
+                }
+                self.decay();
+                continue;
+            }
+
+            // Assumptions are decided first, one per level
+            if let Some(&assumption) = assumptions.get(self.decision_level()) {
+                match value(&self.assignment, assumption) {
+                    Some(false) => break None,
+                    Some(true) => self.trail_lim.push(self.trail.len()),
+                    None => {
+                        self.trail_lim.push(self.trail.len());
+                        self.enqueue(assumption, None);
+                    }
+                }
+                continue;
+            }
+            match self.pick_branch() {
+                Some(var) => {
+                    self.trail_lim.push(self.trail.len());
+                    let lit = match self.phase[var] {
+                        true => Lit::positive(var),
+                        false => Lit::negative(var),
+                    };
+                    self.enqueue(lit, None);
+                }
+                None => {
+                    break Some(
+                        self.assignment
+                            .iter()
+                            .map(|value| *value == Some(true))
+                            .collect(),
+                    )
+                }
+            }
+        };
+        self.cancel_until(0);
+        model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(add: impl Fn(&mut SatSolver, &[Lit]), n: usize, values: u32) -> bool {
+        /* Returns whether the constraints added on n variables accept the given values */
+        let mut solver = SatSolver::new();
+        let lits: Vec<Lit> = (0..n).map(|_| Lit::positive(solver.new_var())).collect();
+        add(&mut solver, &lits);
+        let assumptions: Vec<Lit> = (0..n)
+            .map(|var| match values >> var & 1 {
+                1 => Lit::positive(var),
+                _ => Lit::negative(var),
+            })
+            .collect();
+        solver.solve(&assumptions).is_some()
+    }
+
+    #[test]
+    fn cardinality_constraints_allow_exactly_the_right_counts() {
+        for n in 0..=5 {
+            for values in 0..1u32 << n {
+                let count = values.count_ones() as usize;
+                for k in 0..=n + 1 {
+                    let at_most = |solver: &mut SatSolver, lits: &[Lit]| solver.at_most(lits, k);
+                    assert_eq!(
+                        allows(at_most, n, values),
+                        count <= k,
+                        "at most {} of {}",
+                        k,
+                        n
+                    );
+                    let at_least = |solver: &mut SatSolver, lits: &[Lit]| solver.at_least(lits, k);
+                    assert_eq!(
+                        allows(at_least, n, values),
+                        count >= k,
+                        "at least {} of {}",
+                        k,
+                        n
+                    );
+                }
+                for min in 0..=n {
+                    for max in 0..=n {
+                        let between =
+                            |solver: &mut SatSolver, lits: &[Lit]| solver.between(lits, min, max);
+                        assert_eq!(
+                            allows(between, n, values),
+                            min <= count && count <= max,
+                            "between {} and {} of {}",
+                            min,
+                            max,
+                            n
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn counters_count_the_true_literals() {
+        for n in 0..=6 {
+            for limit in 0..=n + 1 {
+                for values in 0..1u32 << n {
+                    let mut solver = SatSolver::new();
+                    let lits: Vec<Lit> = (0..n).map(|_| Lit::positive(solver.new_var())).collect();
+                    let outputs = solver.counter(&lits, limit);
+                    assert_eq!(outputs.len(), limit.min(n));
+                    let assumptions: Vec<Lit> = (0..n)
+                        .map(|var| match values >> var & 1 {
+                            1 => Lit::positive(var),
+                            _ => Lit::negative(var),
+                        })
+                        .collect();
+                    let model = solver.solve(&assumptions).unwrap();
+                    for (j, output) in outputs.iter().enumerate() {
+                        assert_eq!(
+                            model[output.var()],
+                            values.count_ones() as usize > j,
+                            "output {} of {} with limit {}",
+                            j,
+                            n,
+                            limit
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn models_satisfy_every_clause() {
+        // Overlapping windows of three of six variables, each holding between min and max
+        for min in 0..=3 {
+            for max in min..=3 {
+                let mut solver = SatSolver::new();
+                let lits: Vec<Lit> = (0..6).map(|_| Lit::positive(solver.new_var())).collect();
+                for window in lits.windows(3) {
+                    solver.between(window, min, max);
+                }
+                solver.counter(&lits, 4);
+                for lit in lits.iter().flat_map(|&lit| [lit, !lit]) {
+                    if let Some(model) = solver.solve(&[lit]) {
+                        let holds = |lit: &Lit| model[lit.var()] != lit.is_negative();
+                        assert!(solver.clauses.iter().all(|clause| clause.iter().any(holds)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsatisfiable_clauses_have_no_model() {
+        // Two of three variables true, but no two of them true together
+        let mut solver = SatSolver::new();
+        let lits: Vec<Lit> = (0..3).map(|_| Lit::positive(solver.new_var())).collect();
+        solver.at_least(&lits, 2);
+        for a in 0..3 {
+            for b in a + 1..3 {
+                solver.add_clause(&[!lits[a], !lits[b]]);
+            }
+        }
+        assert_eq!(solver.solve(&[]), None);
+        assert_eq!(solver.solve(&[lits[0]]), None);
+    }
+
+    #[test]
+    fn solves_again_under_other_assumptions() {
+        let mut solver = SatSolver::new();
+        let lits: Vec<Lit> = (0..3).map(|_| Lit::positive(solver.new_var())).collect();
+        solver.between(&lits, 1, 1);
+
+        let model = solver.solve(&[lits[0]]).unwrap();
+        assert_eq!(model[..3], [true, false, false]);
+        // Assumptions that cannot hold together do not make the clauses unsatisfiable
+        assert_eq!(solver.solve(&[lits[0], lits[1]]), None);
+        assert!(solver.solve(&[]).is_some());
+        let model = solver.solve(&[!lits[0], !lits[1]]).unwrap();
+        assert_eq!(model[..3], [false, false, true]);
+    }
+}